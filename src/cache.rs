@@ -0,0 +1,341 @@
+//! A disk cache of extracted documents, so reopening a large PDF or DOCX
+//! does not parse it again.
+//!
+//! Entries are keyed by a hash of the file's content, its extension, the
+//! extraction options and the extractor version, so editing a file or
+//! upgrading rsvp simply misses the cache. The oldest entries are removed once the cache grows past its limit.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+
+use rsvp::document::{Document, Page, Paragraph, Section, Token, TokenFlags};
+use rsvp::extractors::{self, ExtractError, ExtractOptions, Progress};
+
+use crate::utilities;
+
+/// Version of the on-disk layout below.
+const FORMAT_VERSION: u32 = 4;
+
+/// Where extracted documents are stored and how much space they may take.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    /// `None` when caching is off.
+    dir: Option<PathBuf>,
+    max_bytes: u64,
+}
+
+impl Cache {
+    /// A cache in `dir` limited to `max_mb` megabytes; 0 turns it off.
+    pub fn new(dir: Option<&Path>, max_mb: u64) -> Cache {
+        Cache {
+            dir: dir.filter(|_| max_mb > 0).map(|dir| dir.join("documents")),
+            max_bytes: max_mb * 1024 * 1024,
+        }
+    }
+
+    /// Loads `file_path` from the cache, or extracts it with
+    /// [`utilities::get_content`] and stores the result. A document that
+    /// could not be stored is returned with the reason.
+    pub fn get_content(
+        &self,
+        file_path: &str,
+        options: &ExtractOptions,
+        progress: &Progress,
+    ) -> Result<(Document, Option<io::Error>), ExtractError> {
+        let Some(dir) = &self.dir else {
+            return utilities::get_content(file_path, options, progress).map(|document| (document, None));
+        };
+        let key = key(file_path, options).map_err(|e| ExtractError::io(file_path, e))?;
+        let entry = dir.join(format!("{}.json", key));
+
+        if let Some(document) = read_entry(&entry) {
+            return Ok((document, None));
+        }
+
+        let document = utilities::get_content(file_path, options, progress)?;
+        let error = self
+            .store(&entry, &document)
+            .err()
+            .map(|err| io::Error::new(err.kind(), format!("Could not cache '{}': {}", file_path, err)));
+        Ok((document, error))
+    }
+
+    fn store(&self, entry: &Path, document: &Document) -> io::Result<()> {
+        let dir = entry.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)?;
+
+        // Another instance may be writing the same entry; each uses its own
+        // temporary file and the last rename wins.
+        let mut temp_name = entry.as_os_str().to_owned();
+        temp_name.push(format!(".{}.tmp", process::id()));
+        let temp_path = PathBuf::from(temp_name);
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer(&mut writer, &CachedDocument::from(document)).map_err(io::Error::other)?;
+        writer.flush()?;
+        drop(writer);
+
+        if fs::metadata(&temp_path)?.len() > self.max_bytes {
+            // Larger than the whole cache; keep nothing rather than evict everything.
+            return fs::remove_file(&temp_path);
+        }
+        fs::rename(&temp_path, entry)?;
+        self.evict(dir)
+    }
+
+    /// Removes the least recently used entries until the cache fits its limit.
+    fn evict(&self, dir: &Path) -> io::Result<()> {
+        let mut entries: Vec<_> = fs::read_dir(dir)?
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect();
+        entries.sort();
+        self.remove_oldest(entries)
+    }
+
+    /// Removes `entries`, oldest first, until the rest fit the limit.
+    fn remove_oldest(&self, entries: Vec<(SystemTime, u64, PathBuf)>) -> io::Result<()> {
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            // Another instance may have removed it already.
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => total -= len,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deletes every cached document under `cache_dir`.
+pub fn clear(cache_dir: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(cache_dir.join("documents")) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Hex SHA-256 of everything that decides what an extractor produces. The
+/// file is streamed through the hash rather than read into memory.
+fn key(file_path: &str, options: &ExtractOptions) -> io::Result<String> {
+    let extension = Path::new(file_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{}:{}:{}:", FORMAT_VERSION, extractors::VERSION, extension));
+    hasher.update(serde_json::to_string(options).unwrap_or_default());
+    io::copy(&mut BufReader::new(File::open(file_path)?), &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Reads a cache entry, marking it as recently used. Unreadable entries
+/// are deleted and treated as missing.
+fn read_entry(entry: &Path) -> Option<Document> {
+    let file = File::open(entry).ok()?;
+    match serde_json::from_reader::<_, CachedDocument>(BufReader::new(&file)) {
+        Ok(cached) if cached.version == FORMAT_VERSION => {
+            let _ = file.set_modified(SystemTime::now());
+            Some(cached.into())
+        }
+        _ => {
+            let _ = fs::remove_file(entry);
+            None
+        }
+    }
+}
+
+/// A [`Document`] stored compactly, with tuples instead of named fields,
+/// since a long book has hundreds of thousands of tokens.
+#[derive(Serialize, Deserialize)]
+struct CachedDocument {
+    version: u32,
+    title: Option<String>,
+    identifier: Option<String>,
+    encoding: Option<String>,
+    /// Text, offset and [`TokenFlags`] packed into bits.
+    tokens: Vec<(String, usize, u8)>,
+    /// Token range and heading level.
+    paragraphs: Vec<(usize, usize, Option<u8>)>,
+    /// Title, level and token range.
+    sections: Vec<(Option<String>, u8, usize, usize)>,
+    /// Page number and token range.
+    pages: Vec<(u32, usize, usize)>,
+}
+
+impl From<&Document> for CachedDocument {
+    fn from(document: &Document) -> CachedDocument {
+        CachedDocument {
+            version: FORMAT_VERSION,
+            title: document.title.clone(),
+            identifier: document.identifier.clone(),
+            encoding: document.encoding.clone(),
+            tokens: document
+                .tokens
+                .iter()
+                .map(|token| (token.text.clone(), token.offset, pack_flags(token.flags)))
+                .collect(),
+            paragraphs: document
+                .paragraphs
+                .iter()
+                .map(|paragraph| (paragraph.tokens.start, paragraph.tokens.end, paragraph.heading_level))
+                .collect(),
+            sections: document
+                .sections
+                .iter()
+                .map(|section| (section.title.clone(), section.level, section.tokens.start, section.tokens.end))
+                .collect(),
+            pages: document
+                .pages
+                .iter()
+                .map(|page| (page.number, page.tokens.start, page.tokens.end))
+                .collect(),
+        }
+    }
+}
+
+impl From<CachedDocument> for Document {
+    fn from(cached: CachedDocument) -> Document {
+        Document {
+            title: cached.title,
+            identifier: cached.identifier,
+            encoding: cached.encoding,
+            tokens: cached
+                .tokens
+                .into_iter()
+                .map(|(text, offset, flags)| Token { text, offset, flags: unpack_flags(flags) })
+                .collect(),
+            paragraphs: cached
+                .paragraphs
+                .into_iter()
+                .map(|(start, end, heading_level)| Paragraph { tokens: start..end, heading_level })
+                .collect(),
+            sections: cached
+                .sections
+                .into_iter()
+                .map(|(title, level, start, end)| Section { title, level, tokens: start..end })
+                .collect(),
+            pages: cached
+                .pages
+                .into_iter()
+                .map(|(number, start, end)| Page { number, tokens: start..end })
+                .collect(),
+        }
+    }
+}
+
+fn pack_flags(flags: TokenFlags) -> u8 {
+    flags.sentence_end as u8
+        | (flags.paragraph_end as u8) << 1
+        | (flags.heading as u8) << 2
+        | (flags.emphasis as u8) << 3
+}
+
+fn unpack_flags(bits: u8) -> TokenFlags {
+    TokenFlags {
+        sentence_end: bits & 1 != 0,
+        paragraph_end: bits & 1 << 1 != 0,
+        heading: bits & 1 << 2 != 0,
+        emphasis: bits & 1 << 3 != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// An empty directory of its own under the system temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsvp-cache-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(cache: &Cache) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(cache.dir.as_ref().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn stores_and_loads_a_document() {
+        let dir = temp_dir("round-trip");
+        let book = dir.join("book.txt");
+        fs::write(&book, "Call me Ishmael.\n\nSome years ago, never mind how long.").unwrap();
+        let cache = Cache::new(Some(&dir), 1);
+        let options = ExtractOptions::default();
+
+        let (extracted, error) = cache.get_content(book.to_str().unwrap(), &options, &Progress::default()).unwrap();
+        assert!(error.is_none());
+        let [entry] = entries(&cache).try_into().unwrap();
+        assert_eq!(read_entry(&entry), Some(extracted.clone()));
+
+        let (cached, _) = cache.get_content(book.to_str().unwrap(), &options, &Progress::default()).unwrap();
+        assert_eq!(cached, extracted);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_changed_file_misses_the_cache() {
+        let dir = temp_dir("changed");
+        let book = dir.join("book.txt");
+        let cache = Cache::new(Some(&dir), 1);
+        let options = ExtractOptions::default();
+
+        fs::write(&book, "Call me Ishmael.").unwrap();
+        cache.get_content(book.to_str().unwrap(), &options, &Progress::default()).unwrap();
+        fs::write(&book, "Call me Ahab.").unwrap();
+        let (document, _) = cache.get_content(book.to_str().unwrap(), &options, &Progress::default()).unwrap();
+        assert_eq!(document.join(0..3), "Call me Ahab.");
+        assert_eq!(entries(&cache).len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_entries_beyond_max_cache_mb() {
+        let dir = temp_dir("evict");
+        let cache = Cache::new(Some(&dir), 1);
+        let documents = dir.join("documents");
+        fs::create_dir_all(&documents).unwrap();
+        let now = SystemTime::now();
+        for (age, name) in [(3, "oldest"), (2, "older"), (1, "newest")] {
+            let file = File::create(documents.join(format!("{}.json", name))).unwrap();
+            file.set_len(400 * 1024).unwrap();
+            file.set_modified(now - Duration::from_secs(age * 60)).unwrap();
+        }
+
+        cache.evict(&documents).unwrap();
+        assert_eq!(entries(&cache), [documents.join("newest.json"), documents.join("older.json")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicting_an_entry_already_removed_is_not_an_error() {
+        let dir = temp_dir("evict-missing");
+        let cache = Cache::new(Some(&dir), 1);
+        // Listed, then removed by another instance before this one got to it.
+        let entries = vec![(SystemTime::now(), 2 * 1024 * 1024, dir.join("gone.json"))];
+        assert!(cache.remove_oldest(entries).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,428 @@
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+
+/// Abbreviations that end in a full stop without ending the sentence.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "vs", "etc", "e.g", "i.e", "cf", "fig",
+    "vol", "pp",
+];
+
+/// Flags carried by every token so the reader can pause and navigate
+/// without looking at the source file again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TokenFlags {
+    pub sentence_end: bool,
+    pub paragraph_end: bool,
+    pub heading: bool,
+    pub emphasis: bool,
+}
+
+/// A single word as it will be flashed on screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub text: String,
+    /// Byte offset of the word in the extracted text stream.
+    pub offset: usize,
+    pub flags: TokenFlags,
+}
+
+/// A run of tokens between two paragraph breaks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paragraph {
+    pub tokens: Range<usize>,
+    pub heading_level: Option<u8>,
+}
+
+/// A chapter or heading-delimited part of the document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub title: Option<String>,
+    pub level: u8,
+    pub tokens: Range<usize>,
+}
+
+/// A printed page, for formats that have them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    /// The page's number in the file, counting from 1.
+    pub number: u32,
+    pub tokens: Range<usize>,
+}
+
+/// The text of a book or page, broken into sections, paragraphs and tokens.
+///
+/// Tokens are stored flat so `current_word_index` can index them directly;
+/// sections and paragraphs refer to ranges of that list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub title: Option<String>,
+    /// An identifier the source gives itself, such as an EPUB's unique
+    /// identifier, which stays the same across editions of the file.
+    pub identifier: Option<String>,
+    /// The character encoding a text file was decoded from.
+    pub encoding: Option<String>,
+    pub tokens: Vec<Token>,
+    pub paragraphs: Vec<Paragraph>,
+    pub sections: Vec<Section>,
+    /// Pages in order; empty when the source has none.
+    pub pages: Vec<Page>,
+}
+
+impl Document {
+    /// Builds a document from plain text, treating blank lines as paragraph breaks.
+    pub fn from_text(text: &str) -> Document {
+        let mut builder = DocumentBuilder::new();
+        builder.push_plain_text(text);
+        builder.finish()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// A stable name for this document that does not depend on where it was
+    /// loaded from: a hash of its own identifier when it has one, otherwise
+    /// of its words, ignoring case, punctuation and layout, so the file can
+    /// be re-saved or converted and keep its name. Long books take a while
+    /// to hash, so work this out off the UI thread.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        match &self.identifier {
+            Some(identifier) => hasher.update(format!("id:{}", identifier.trim())),
+            None => {
+                hasher.update("words:");
+                for token in &self.tokens {
+                    let word: String = token.text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect();
+                    if !word.is_empty() {
+                        hasher.update(word);
+                        hasher.update(" ");
+                    }
+                }
+            }
+        }
+        hasher
+            .finalize()
+            .iter()
+            .take(16)
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Joins the words in `range` with single spaces, clamping to the document.
+    pub fn join(&self, range: Range<usize>) -> String {
+        let end = range.end.min(self.tokens.len());
+        let start = range.start.min(end);
+        self.tokens[start..end]
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the index of the section containing word `index`.
+    pub fn section_at(&self, index: usize) -> Option<usize> {
+        self.sections
+            .iter()
+            .rposition(|section| section.tokens.start <= index)
+    }
+
+    /// Returns the first word of the next section after word `index`.
+    pub fn next_section_start(&self, index: usize) -> Option<usize> {
+        self.sections
+            .iter()
+            .map(|section| section.tokens.start)
+            .find(|&start| start > index)
+    }
+
+    /// Returns the start of the current section, or of the previous one when
+    /// `index` is already within the first few words of the current section.
+    pub fn previous_section_start(&self, index: usize) -> Option<usize> {
+        const RESTART_WINDOW: usize = 10;
+        let current = self.section_at(index)?;
+        let start = self.sections[current].tokens.start;
+        if index >= start + RESTART_WINDOW || current == 0 {
+            Some(start)
+        } else {
+            Some(self.sections[current - 1].tokens.start)
+        }
+    }
+
+    /// Returns the number of the page containing word `index`.
+    pub fn page_at(&self, index: usize) -> Option<u32> {
+        let page = self.pages.partition_point(|page| page.tokens.end <= index);
+        self.pages.get(page).or(self.pages.last()).map(|page| page.number)
+    }
+
+    /// Returns the first word on page `number`, or after it when the page
+    /// has no text.
+    pub fn page_start(&self, number: u32) -> Option<usize> {
+        let page = self.pages.iter().find(|page| page.number == number)?;
+        Some(page.tokens.start.min(self.tokens.len().saturating_sub(1)))
+    }
+
+    /// Returns the index of the first word of the sentence containing `index`.
+    pub fn sentence_start(&self, index: usize) -> usize {
+        let index = index.min(self.tokens.len());
+        self.tokens[..index]
+            .iter()
+            .rposition(|token| token.flags.sentence_end)
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    }
+
+    /// Returns the index one past the end of the sentence containing `index`.
+    pub fn sentence_end(&self, index: usize) -> usize {
+        self.tokens
+            .iter()
+            .enumerate()
+            .skip(index)
+            .find(|(_, token)| token.flags.sentence_end)
+            .map(|(i, _)| i + 1)
+            .unwrap_or(self.tokens.len())
+    }
+}
+
+/// Incrementally assembles a [`Document`] while an extractor walks its source.
+pub struct DocumentBuilder {
+    document: Document,
+    offset: usize,
+    paragraph_start: usize,
+    heading_level: Option<u8>,
+    emphasis: bool,
+    /// Whether the last text pushed ended in whitespace, i.e. the next run
+    /// starts a new word instead of continuing the previous one.
+    at_word_boundary: bool,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        DocumentBuilder {
+            document: Document::default(),
+            offset: 0,
+            paragraph_start: 0,
+            heading_level: None,
+            emphasis: false,
+            at_word_boundary: true,
+        }
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.document.title = title;
+    }
+
+    pub fn set_identifier(&mut self, identifier: Option<String>) {
+        self.document.identifier = identifier.filter(|identifier| !identifier.trim().is_empty());
+    }
+
+    /// Closes the current section and opens a new one at the next token.
+    pub fn start_section(&mut self, title: Option<String>, level: u8) {
+        self.end_paragraph();
+        let start = self.document.tokens.len();
+        if let Some(last) = self.document.sections.last_mut() {
+            last.tokens.end = start;
+            // An untitled section with nothing in it is just the implicit
+            // leading section; replace it instead of keeping an empty entry.
+            if last.tokens.is_empty() && last.title.is_none() {
+                self.document.sections.pop();
+            }
+        }
+        self.document.sections.push(Section { title, level, tokens: start..start });
+    }
+
+    /// Closes the current page and opens page `number` at the next token.
+    /// Paragraphs carry on across pages.
+    pub fn start_page(&mut self, number: u32) {
+        let start = self.document.tokens.len();
+        if let Some(last) = self.document.pages.last_mut() {
+            last.tokens.end = start;
+        }
+        self.document.pages.push(Page { number, tokens: start..start });
+    }
+
+    /// Starts a heading paragraph; it ends at the next [`end_paragraph`](Self::end_paragraph).
+    pub fn start_heading(&mut self, level: u8) {
+        self.end_paragraph();
+        self.heading_level = Some(level);
+    }
+
+    pub fn set_emphasis(&mut self, emphasis: bool) {
+        self.emphasis = emphasis;
+    }
+
+    /// Appends a run of text to the current paragraph.
+    ///
+    /// Runs are joined without inserting spaces, so a word split over two
+    /// runs (`dar` + `k`) becomes a single token.
+    pub fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.document.sections.is_empty() {
+            self.document.sections.push(Section { title: None, level: 0, tokens: 0..0 });
+        }
+
+        let flags = TokenFlags {
+            heading: self.heading_level.is_some(),
+            emphasis: self.emphasis,
+            ..TokenFlags::default()
+        };
+
+        for (start, word) in word_spans(text) {
+            let continues_word = start == 0
+                && !self.at_word_boundary
+                && self.document.tokens.len() > self.paragraph_start;
+            if continues_word {
+                let last = self.document.tokens.last_mut().unwrap();
+                last.text.push_str(word);
+                last.flags.emphasis |= flags.emphasis;
+            } else {
+                self.document.tokens.push(Token {
+                    text: word.to_string(),
+                    offset: self.offset + start,
+                    flags,
+                });
+            }
+        }
+
+        self.at_word_boundary = text.ends_with(char::is_whitespace);
+        self.offset += text.len();
+    }
+
+    /// Appends plain text, treating blank lines as paragraph breaks.
+    pub fn push_plain_text(&mut self, text: &str) {
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                self.end_paragraph();
+            } else {
+                self.push_text(line);
+                self.push_text("\n");
+            }
+        }
+        self.end_paragraph();
+    }
+
+    /// Ends the current paragraph, marking sentence ends within it.
+    pub fn end_paragraph(&mut self) {
+        let start = self.paragraph_start;
+        let end = self.document.tokens.len();
+        if end > start {
+            for token in &mut self.document.tokens[start..end] {
+                token.flags.sentence_end = ends_sentence(&token.text);
+            }
+            let last = &mut self.document.tokens[end - 1].flags;
+            last.sentence_end = true;
+            last.paragraph_end = true;
+
+            self.document.paragraphs.push(Paragraph {
+                tokens: start..end,
+                heading_level: self.heading_level,
+            });
+            // Keep offsets of separate paragraphs apart.
+            self.offset += 1;
+        }
+        self.paragraph_start = end;
+        self.heading_level = None;
+        self.at_word_boundary = true;
+    }
+
+    pub fn finish(mut self) -> Document {
+        self.end_paragraph();
+        let total = self.document.tokens.len();
+        if let Some(last) = self.document.sections.last_mut() {
+            last.tokens.end = total;
+        }
+        if let Some(last) = self.document.pages.last_mut() {
+            last.tokens.end = total;
+        }
+        self.document
+    }
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Splits `text` on whitespace, yielding each word with its byte offset.
+fn word_spans(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(char::is_whitespace)
+        .filter(|word| !word.is_empty())
+        .map(move |word| (word.as_ptr() as usize - text.as_ptr() as usize, word))
+}
+
+/// Decides whether a word closes a sentence, ignoring trailing quotes and
+/// brackets and skipping common abbreviations and initials.
+fn ends_sentence(word: &str) -> bool {
+    let trimmed = word.trim_end_matches(['"', '\'', ')', ']', '”', '’', '»']);
+    if trimmed.ends_with(['!', '?', '…']) {
+        return true;
+    }
+    let Some(stem) = trimmed.strip_suffix('.') else {
+        return false;
+    };
+    let stem = stem.trim_start_matches(['"', '\'', '(', '[', '“', '‘', '«']);
+    let is_initial = stem.chars().count() == 1 && stem.chars().all(char::is_uppercase);
+    !is_initial && !ABBREVIATIONS.contains(&stem.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pages 1 and 3 have two words each; pages 2 and 4 are empty.
+    fn document_with_empty_pages() -> Document {
+        let mut builder = DocumentBuilder::new();
+        builder.start_page(1);
+        builder.push_text("one two");
+        builder.start_page(2);
+        builder.start_page(3);
+        builder.push_text(" three four");
+        builder.start_page(4);
+        builder.finish()
+    }
+
+    #[test]
+    fn fingerprint_ignores_case_punctuation_and_layout() {
+        let original = Document::from_text("“Call me Ishmael,” he said.");
+        let resaved = Document::from_text("\"Call me\n\nIshmael\" -- he said");
+        assert_eq!(original.fingerprint(), resaved.fingerprint());
+        assert_ne!(original.fingerprint(), Document::from_text("Call me Ahab, he said.").fingerprint());
+    }
+
+    #[test]
+    fn fingerprint_prefers_the_identifier() {
+        let edition = |text: &str| {
+            let mut builder = DocumentBuilder::new();
+            builder.set_identifier(Some("urn:isbn:9780142437247".to_string()));
+            builder.push_text(text);
+            builder.finish()
+        };
+        assert_eq!(edition("Call me Ishmael.").fingerprint(), edition("Call me Ishmael, revised.").fingerprint());
+    }
+
+    #[test]
+    fn page_at_skips_empty_pages() {
+        let document = document_with_empty_pages();
+        let pages: Vec<_> = (0..document.len()).map(|index| document.page_at(index)).collect();
+        assert_eq!(pages, [Some(1), Some(1), Some(3), Some(3)]);
+        // Past the last word is the end of the book, the last page.
+        assert_eq!(document.page_at(document.len()), Some(4));
+        assert_eq!(Document::default().page_at(0), None);
+    }
+
+    #[test]
+    fn page_start_of_an_empty_page_is_the_next_word() {
+        let document = document_with_empty_pages();
+        assert_eq!(document.page_start(1), Some(0));
+        assert_eq!(document.page_start(2), Some(2));
+        assert_eq!(document.page_start(3), Some(2));
+        // Nothing follows the last page, so its start is the last word.
+        assert_eq!(document.page_start(4), Some(3));
+        assert_eq!(document.page_start(5), None);
+    }
+}
@@ -0,0 +1,358 @@
+//! Finds the main article of a web page, in the manner of Readability:
+//! paragraphs score their ancestors by how much prose they hold, and the
+//! best-scoring element is kept with any siblings that look like part of it.
+
+use std::collections::HashMap;
+
+use ego_tree::NodeId;
+use scraper::{ElementRef, Html, Node, Selector};
+
+use super::markup::SKIPPED_ELEMENTS;
+
+/// Elements that hold page furniture rather than the article.
+const BOILERPLATE_ELEMENTS: &[&str] = &["aside", "button", "dialog", "footer", "form", "menu", "nav", "select"];
+
+/// ARIA roles of page furniture.
+const BOILERPLATE_ROLES: &[&str] = &[
+    "alert", "banner", "complementary", "contentinfo", "dialog", "menu", "navigation", "search",
+];
+
+/// Words in a class or id that mark page furniture. Words of four letters
+/// or more also match as the start of a part, so "comment" finds
+/// "comments-list".
+const UNLIKELY_NAMES: &[&str] = &[
+    "ad", "ads", "advert", "banner", "breadcrumb", "comment", "consent", "cookie", "disqus",
+    "footer", "gdpr", "masthead", "menu", "modal", "nav", "navbar", "navigation", "newsletter",
+    "outbrain", "popup", "promo", "related", "share", "sidebar", "social", "sponsor", "subscribe",
+    "taboola", "tags", "toolbar", "widget",
+];
+
+/// Words in a class or id that mark the article.
+const LIKELY_NAMES: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+
+/// Elements whose text counts as a paragraph of the article.
+const PARAGRAPH_ELEMENTS: &[&str] = &["p", "pre", "td", "blockquote"];
+
+/// Elements that break a `div` into paragraphs of its own.
+const BLOCK_CHILDREN: &[&str] = &[
+    "article", "blockquote", "div", "dl", "ol", "p", "pre", "section", "table", "ul",
+];
+
+/// Shorter paragraphs are captions, bylines and buttons, and score nothing.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+/// Below this much text the page has no article worth picking out.
+const MIN_ARTICLE_CHARS: usize = 250;
+/// Lists and blocks inside the article that are mostly links are dropped.
+const MAX_LINK_DENSITY: f64 = 0.5;
+
+/// How much visible text each element of a page holds and how much of it
+/// is inside links, counted in one pass over the page so that looking it
+/// up does not walk the element again. Each word counts its characters and
+/// one space.
+pub struct TextLengths {
+    lengths: HashMap<NodeId, (usize, usize)>,
+}
+
+impl TextLengths {
+    pub fn new(document: &Html) -> TextLengths {
+        let mut lengths = TextLengths { lengths: HashMap::new() };
+        lengths.count(document.root_element());
+        lengths
+    }
+
+    /// Counts the children of `element` before the element itself,
+    /// returning its text and link lengths.
+    fn count(&mut self, element: ElementRef) -> (usize, usize) {
+        let (mut text, mut links) = (0, 0);
+        if !SKIPPED_ELEMENTS.contains(&element.value().name()) {
+            for child in element.children() {
+                match child.value() {
+                    Node::Text(words) => {
+                        text += words.split_whitespace().map(|word| word.chars().count() + 1).sum::<usize>();
+                    }
+                    Node::Element(_) => {
+                        if let Some(child) = ElementRef::wrap(child) {
+                            let (child_text, child_links) = self.count(child);
+                            text += child_text;
+                            links += child_links;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if element.value().name() == "a" {
+            links = text;
+        }
+        self.lengths.insert(element.id(), (text, links));
+        (text, links)
+    }
+
+    /// The length of the visible text of `element`.
+    pub fn text(&self, element: ElementRef) -> usize {
+        self.lengths.get(&element.id()).map_or(0, |&(text, _)| text)
+    }
+
+    /// The share of the visible text of `element` that is inside links.
+    pub fn link_density(&self, element: ElementRef) -> f64 {
+        match self.lengths.get(&element.id()) {
+            Some(&(text, links)) if text > 0 => links as f64 / text as f64,
+            _ => 0.0,
+        }
+    }
+}
+
+/// The elements that make up the page's main article, in document order,
+/// or `None` when no part of the page stands out as one.
+pub fn main_content<'a>(document: &'a Html, lengths: &TextLengths) -> Option<Vec<ElementRef<'a>>> {
+    let body_selector = Selector::parse("body").unwrap();
+    let body = document.select(&body_selector).next()?;
+    let is_boilerplate = |element| is_boilerplate(element, lengths);
+
+    let mut scores = HashMap::new();
+    for element in body.descendent_elements() {
+        if !is_paragraph(element) || element.ancestors().filter_map(ElementRef::wrap).any(is_boilerplate) {
+            continue;
+        }
+        let length = lengths.text(element);
+        if is_boilerplate(element) || length < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let text = visible_text(element);
+
+        let score = 1.0 + text.matches([',', '，']).count() as f64 + (length / 100).min(3) as f64;
+        let ancestors = element.ancestors().filter_map(ElementRef::wrap).take(3);
+        for (level, ancestor) in ancestors.enumerate() {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                _ => level as f64 * 3.0,
+            };
+            *scores.entry(ancestor.id()).or_insert_with(|| initial_score(ancestor)) += score / divider;
+        }
+    }
+
+    let (top, top_score) = scores
+        .iter()
+        .filter_map(|(&id, &score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            Some((element, score * (1.0 - lengths.link_density(element))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if lengths.text(top) < MIN_ARTICLE_CHARS {
+        return None;
+    }
+
+    // The article is often split over siblings, say by an advert between
+    // two of its parts.
+    let Some(parent) = top.parent().and_then(ElementRef::wrap) else {
+        return Some(vec![top]);
+    };
+    let threshold = (top_score * 0.2).max(10.0);
+    let content = parent
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|&sibling| {
+            if sibling == top {
+                return true;
+            }
+            if is_boilerplate(sibling) {
+                return false;
+            }
+            let score = scores.get(&sibling.id()).map_or(0.0, |score| score * (1.0 - lengths.link_density(sibling)));
+            if score >= threshold {
+                return true;
+            }
+            if sibling.value().name() != "p" {
+                return false;
+            }
+            let density = lengths.link_density(sibling);
+            let length = lengths.text(sibling);
+            (length > 80 && density < 0.25) || (length > 0 && density == 0.0 && visible_text(sibling).contains(". "))
+        })
+        .collect();
+    Some(content)
+}
+
+/// Whether `element` is page furniture: navigation, a sidebar, a footer,
+/// a cookie banner, a hidden element, or a list that is mostly links.
+pub fn is_boilerplate(element: ElementRef, lengths: &TextLengths) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if matches!(name, "html" | "body" | "article" | "main") {
+        return false;
+    }
+    if BOILERPLATE_ELEMENTS.contains(&name)
+        || value.attr("role").is_some_and(|role| BOILERPLATE_ROLES.contains(&role))
+        || value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+        || value
+            .attr("style")
+            .is_some_and(|style| style.replace(' ', "").contains("display:none"))
+    {
+        return true;
+    }
+
+    let names = class_and_id(element);
+    if contains_any(&names, UNLIKELY_NAMES) && !contains_any(&names, LIKELY_NAMES) {
+        return true;
+    }
+    matches!(name, "ul" | "ol" | "div" | "section" | "table") && lengths.link_density(element) > MAX_LINK_DENSITY
+}
+
+/// Whether the text of `element` reads as a paragraph: a paragraph-like
+/// element, or a `div` holding text directly.
+fn is_paragraph(element: ElementRef) -> bool {
+    let name = element.value().name();
+    PARAGRAPH_ELEMENTS.contains(&name)
+        || (name == "div"
+            && !element
+                .children()
+                .filter_map(ElementRef::wrap)
+                .any(|child| BLOCK_CHILDREN.contains(&child.value().name())))
+}
+
+/// The score an element starts with before its paragraphs are counted.
+fn initial_score(element: ElementRef) -> f64 {
+    let tag = match element.value().name() {
+        "article" => 10.0,
+        "div" | "main" => 5.0,
+        "blockquote" | "pre" | "td" => 3.0,
+        "address" | "dl" | "dd" | "dt" | "form" | "li" | "ol" | "ul" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let names = class_and_id(element);
+    let mut weight = 0.0;
+    if contains_any(&names, UNLIKELY_NAMES) {
+        weight -= 25.0;
+    }
+    if contains_any(&names, LIKELY_NAMES) {
+        weight += 25.0;
+    }
+    tag + weight
+}
+
+/// The text of `element` as shown, without scripts or styles, with
+/// whitespace collapsed.
+fn visible_text(element: ElementRef) -> String {
+    let mut words = Vec::new();
+    collect_text(element, &mut words);
+    words.join(" ")
+}
+
+fn collect_text<'a>(element: ElementRef<'a>, words: &mut Vec<&'a str>) {
+    if SKIPPED_ELEMENTS.contains(&element.value().name()) {
+        return;
+    }
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => words.extend(text.split_whitespace()),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, words);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The parts of the class and id of `element`, lower-cased and split at
+/// spaces, hyphens and underscores.
+fn class_and_id(element: ElementRef) -> Vec<String> {
+    let value = element.value();
+    value
+        .classes()
+        .chain(value.id())
+        .flat_map(|name| name.split(['-', '_']))
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn contains_any(names: &[String], words: &[&str]) -> bool {
+    names
+        .iter()
+        .any(|name| words.iter().any(|word| name == word || (word.len() >= 4 && name.starts_with(word))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROSE: &str = "The river rose all through the night, and by morning the lower town was under water, \
+        its streets turned to canals and its cellars to wells.";
+
+    fn page(body: &str) -> Html {
+        Html::parse_document(&format!("<html><head><title>Flood</title></head><body>{}</body></html>", body))
+    }
+
+    fn select<'a>(document: &'a Html, selector: &str) -> ElementRef<'a> {
+        document.select(&Selector::parse(selector).unwrap()).next().unwrap()
+    }
+
+    fn content_text(document: &Html) -> Option<String> {
+        let lengths = TextLengths::new(document);
+        let elements = main_content(document, &lengths)?;
+        Some(elements.into_iter().map(visible_text).collect::<Vec<_>>().join(" "))
+    }
+
+    #[test]
+    fn counts_text_and_link_lengths() {
+        let document = page("<div id=\"box\">one two <a href=\"/\">three</a><script>var x;</script></div>");
+        let lengths = TextLengths::new(&document);
+        let element = select(&document, "#box");
+        assert_eq!(lengths.text(element), 14);
+        assert_eq!(lengths.link_density(element), 6.0 / 14.0);
+        assert_eq!(lengths.text(select(&document, "script")), 0);
+    }
+
+    #[test]
+    fn finds_the_article_between_navigation_and_footer() {
+        let document = page(&format!(
+            "<nav><ul><li><a href=\"/\">Home</a></li><li><a href=\"/news\">News</a></li></ul></nav>\
+             <div class=\"content\"><h1>Flood</h1><p>{0}</p><p>{0}</p><p>{0}</p></div>\
+             <footer><p>Copyright, all rights reserved, by the Gazette, since long ago.</p></footer>",
+            PROSE
+        ));
+        let text = content_text(&document).unwrap();
+        assert!(text.contains("The river rose"));
+        assert!(!text.contains("Home"));
+        assert!(!text.contains("Copyright"));
+    }
+
+    #[test]
+    fn navigation_is_boilerplate() {
+        let document = page(
+            "<nav><a href=\"/\">Home</a></nav>\
+             <ul id=\"links\"><li><a href=\"/a\">Politics</a></li><li><a href=\"/b\">Sport</a></li></ul>",
+        );
+        let lengths = TextLengths::new(&document);
+        assert!(is_boilerplate(select(&document, "nav"), &lengths));
+        assert!(is_boilerplate(select(&document, "#links"), &lengths));
+    }
+
+    #[test]
+    fn a_cookie_banner_is_left_out() {
+        let document = page(&format!(
+            "<div class=\"cookie-banner\"><p>We use cookies to improve your experience, to measure traffic, \
+             and to show adverts. By using this site you agree to this.</p></div>\
+             <article><p>{0}</p><p>{0}</p><p>{0}</p></article>",
+            PROSE
+        ));
+        let lengths = TextLengths::new(&document);
+        assert!(is_boilerplate(select(&document, ".cookie-banner"), &lengths));
+        let text = content_text(&document).unwrap();
+        assert!(text.contains("The river rose"));
+        assert!(!text.contains("cookies"));
+    }
+
+    #[test]
+    fn a_page_without_an_article_has_no_main_content() {
+        let document = page("<h1>Welcome</h1><p>Short note, nothing more here at all.</p><a href=\"/\">Enter</a>");
+        assert_eq!(content_text(&document), None);
+    }
+}
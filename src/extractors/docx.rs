@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::fs::File;
+use xml::reader::{EventReader, XmlEvent};
+use zip::read::ZipArchive;
+
+use super::{attribute, flush, read_entry, ExtractError, ExtractOptions, NoteMode, Runs};
+use crate::document::{Document, DocumentBuilder};
+
+/// Reads the body of a DOCX file, opening a section at every paragraph
+/// styled as a heading (`Heading1`..`Heading9`, `Title`) and numbering list
+/// items as Word shows them. Footnotes and endnotes are read after the
+/// paragraph that refers to them, or left out, as `options` ask.
+pub fn extract_docx(file_path: &str, options: &ExtractOptions) -> Result<Document, ExtractError> {
+    let file = File::open(file_path).map_err(|e| ExtractError::io(file_path, e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| ExtractError::zip(file_path, "DOCX", e))?;
+
+    let body = read_entry(&mut archive, file_path, "word/document.xml")?
+        .ok_or_else(|| ExtractError::corrupt(file_path, "DOCX has no word/document.xml"))?;
+    let heading_styles = read_entry(&mut archive, file_path, "word/styles.xml")?
+        .map(|styles| heading_styles(&styles))
+        .unwrap_or_default();
+    let list_levels = read_entry(&mut archive, file_path, "word/numbering.xml")?
+        .map(|numbering| list_levels(&numbering))
+        .unwrap_or_default();
+
+    let mut notes: HashMap<(NoteKind, String), Vec<DocxParagraph>> = HashMap::new();
+    if options.notes == NoteMode::Inline {
+        for (kind, name) in [(NoteKind::Footnote, "word/footnotes.xml"), (NoteKind::Endnote, "word/endnotes.xml")] {
+            let Some(xml) = read_entry(&mut archive, file_path, name)? else {
+                continue;
+            };
+            for paragraph in parse_paragraphs(&xml, file_path)? {
+                if let Some(id) = paragraph.note.clone() {
+                    notes.entry((kind, id)).or_default().push(paragraph);
+                }
+            }
+        }
+    }
+
+    let mut builder = DocumentBuilder::new();
+    let mut list_counters: HashMap<(String, u8), usize> = HashMap::new();
+    let mut note_numbers: HashMap<NoteKind, usize> = HashMap::new();
+
+    for paragraph in parse_paragraphs(&body, file_path)? {
+        let level = paragraph.outline_level.or_else(|| {
+            let style = paragraph.style.as_deref()?;
+            heading_styles.get(style).copied().or_else(|| heading_level(style))
+        });
+        let marker = paragraph
+            .numbering
+            .as_ref()
+            .and_then(|(id, level)| list_marker(&list_levels, &mut list_counters, id, *level));
+
+        if let Some(level) = level {
+            let title = paragraph.text(marker.as_deref());
+            if !title.is_empty() {
+                builder.start_section(Some(title), level);
+                builder.start_heading(level);
+            }
+        }
+        if let Some(marker) = &marker {
+            builder.push_text(marker);
+            builder.push_text(" ");
+        }
+
+        // Notes are numbered in the order they are referred to, as Word does.
+        let mut referenced = Vec::new();
+        for run in &paragraph.runs {
+            match run {
+                Run::Text(text, emphasis) => {
+                    builder.set_emphasis(*emphasis);
+                    builder.push_text(text);
+                }
+                Run::Note(kind, id) if notes.contains_key(&(*kind, id.clone())) => {
+                    let number = note_numbers.entry(*kind).or_default();
+                    *number += 1;
+                    let label = kind.label(*number);
+                    builder.set_emphasis(false);
+                    builder.push_text(&format!("[{}]", label));
+                    referenced.push((&notes[&(*kind, id.clone())], label));
+                }
+                Run::Note(..) => {}
+            }
+        }
+        builder.set_emphasis(false);
+        builder.end_paragraph();
+
+        for (note, label) in referenced {
+            for (i, note_paragraph) in note.iter().enumerate() {
+                if i == 0 {
+                    builder.push_text(&format!("[{}] ", label));
+                }
+                for run in &note_paragraph.runs {
+                    if let Run::Text(text, emphasis) = run {
+                        builder.set_emphasis(*emphasis);
+                        builder.push_text(text);
+                    }
+                }
+                builder.set_emphasis(false);
+                builder.end_paragraph();
+            }
+        }
+    }
+
+    let document = builder.finish();
+    if document.is_empty() {
+        return Err(ExtractError::Empty { path: file_path.to_string() });
+    }
+    Ok(document)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum NoteKind {
+    Footnote,
+    Endnote,
+}
+
+impl NoteKind {
+    /// Footnotes are numbered 1, 2, 3 and endnotes i, ii, iii, like Word's defaults.
+    fn label(self, number: usize) -> String {
+        match self {
+            NoteKind::Footnote => number.to_string(),
+            NoteKind::Endnote => roman(number).to_lowercase(),
+        }
+    }
+}
+
+/// A piece of a paragraph: text that is or is not bold or italic, or a
+/// reference to a note.
+enum Run {
+    Text(String, bool),
+    Note(NoteKind, String),
+}
+
+/// A paragraph from one of the XML parts of a DOCX file.
+#[derive(Default)]
+struct DocxParagraph {
+    style: Option<String>,
+    outline_level: Option<u8>,
+    /// Numbering id and indent level, for list items.
+    numbering: Option<(String, u8)>,
+    runs: Vec<Run>,
+    /// The footnote or endnote the paragraph belongs to.
+    note: Option<String>,
+}
+
+impl Runs for DocxParagraph {
+    fn push_run(&mut self, text: String, emphasis: bool) {
+        self.runs.push(Run::Text(text, emphasis));
+    }
+}
+
+impl DocxParagraph {
+    /// The paragraph's text with whitespace collapsed, after `marker`.
+    fn text(&self, marker: Option<&str>) -> String {
+        let mut text = marker.map(|marker| format!("{} ", marker)).unwrap_or_default();
+        for run in &self.runs {
+            if let Run::Text(run, _) = run {
+                text.push_str(run);
+            }
+        }
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Splits a WordprocessingML part into paragraphs, joining runs the way
+/// Word displays them: tabs and line breaks become spaces, and deleted
+/// text, field codes and fallback copies of drawings are left out.
+fn parse_paragraphs(xml: &str, file_path: &str) -> Result<Vec<DocxParagraph>, ExtractError> {
+    let mut paragraphs = Vec::new();
+    // Text boxes put paragraphs inside paragraphs.
+    let mut open: Vec<DocxParagraph> = Vec::new();
+    let mut note: Option<String> = None;
+    let mut text = String::new();
+    let mut bold = false;
+    let mut italic = false;
+    let mut in_run = false;
+    let mut in_run_properties = false;
+    let mut in_text = false;
+    let mut fallback_depth = 0;
+
+    for event in EventReader::from_str(xml) {
+        let event = event.map_err(|e| ExtractError::corrupt(file_path, format!("malformed DOCX XML: {}", e)))?;
+        if fallback_depth > 0 {
+            match &event {
+                XmlEvent::StartElement { name, .. } if name.local_name == "Fallback" => fallback_depth += 1,
+                XmlEvent::EndElement { name } if name.local_name == "Fallback" => fallback_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |key: &str| attribute(&attributes, key);
+                match name.local_name.as_str() {
+                    "Fallback" => fallback_depth = 1,
+                    // Separators between the text and the notes have a type; notes do not.
+                    "footnote" | "endnote" => note = attr("type").is_none().then(|| attr("id")).flatten(),
+                    "p" => {
+                        flush(&mut text, bold || italic, open.last_mut());
+                        open.push(DocxParagraph { note: note.clone(), ..DocxParagraph::default() });
+                    }
+                    "pStyle" => {
+                        if let Some(paragraph) = open.last_mut() {
+                            paragraph.style = attr("val");
+                        }
+                    }
+                    "outlineLvl" => {
+                        // Level 9 is Word's "body text", i.e. not in the outline.
+                        let level = attr("val").and_then(|v| v.parse::<u8>().ok()).filter(|&l| l < 9);
+                        if let (Some(paragraph), Some(level)) = (open.last_mut(), level) {
+                            paragraph.outline_level = Some(level + 1);
+                        }
+                    }
+                    "numId" | "ilvl" if !in_run => {
+                        if let Some(paragraph) = open.last_mut() {
+                            let numbering = paragraph.numbering.get_or_insert_with(|| (String::new(), 0));
+                            match name.local_name.as_str() {
+                                "numId" => numbering.0 = attr("val").unwrap_or_default(),
+                                _ => numbering.1 = attr("val").and_then(|v| v.parse().ok()).unwrap_or(0),
+                            }
+                        }
+                    }
+                    "r" => {
+                        in_run = true;
+                        bold = false;
+                        italic = false;
+                    }
+                    "rPr" if in_run => in_run_properties = true,
+                    "b" | "i" if in_run_properties => {
+                        let on = !matches!(attr("val").as_deref(), Some("0" | "false" | "off"));
+                        if name.local_name == "b" {
+                            bold = on;
+                        } else {
+                            italic = on;
+                        }
+                    }
+                    "t" if in_run => in_text = true,
+                    "tab" | "br" | "cr" if in_run => text.push(' '),
+                    "noBreakHyphen" if in_run => text.push('-'),
+                    "footnoteReference" | "endnoteReference" => {
+                        let kind = if name.local_name == "footnoteReference" { NoteKind::Footnote } else { NoteKind::Endnote };
+                        flush(&mut text, bold || italic, open.last_mut());
+                        if let (Some(paragraph), Some(id)) = (open.last_mut(), attr("id")) {
+                            paragraph.runs.push(Run::Note(kind, id));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::Whitespace(chars) if in_text => text.push_str(&chars),
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "t" => in_text = false,
+                "rPr" => in_run_properties = false,
+                "r" => {
+                    flush(&mut text, bold || italic, open.last_mut());
+                    in_run = false;
+                }
+                "p" => {
+                    flush(&mut text, bold || italic, open.last_mut());
+                    paragraphs.extend(open.pop());
+                }
+                "footnote" | "endnote" => note = None,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(paragraphs)
+}
+
+/// Maps style ids to heading levels using the style names in `styles.xml`,
+/// so localised documents (where the id is not `Heading1`) still work.
+fn heading_styles(styles: &str) -> HashMap<String, u8> {
+    let mut levels = HashMap::new();
+    let mut current_id: Option<String> = None;
+
+    for event in EventReader::from_str(styles).into_iter().flatten() {
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |key: &str| attribute(&attributes, key);
+                match name.local_name.as_str() {
+                    "style" => current_id = attr("styleId"),
+                    "name" => {
+                        if let (Some(id), Some(style_name)) = (&current_id, attr("val")) {
+                            if let Some(level) = heading_level(&style_name.replace(' ', "")) {
+                                levels.insert(id.clone(), level);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } if name.local_name == "style" => current_id = None,
+            _ => {}
+        }
+    }
+
+    levels
+}
+
+/// Parses `Heading1`..`Heading9` (any case) and `Title` into a level.
+fn heading_level(style: &str) -> Option<u8> {
+    let lower = style.to_lowercase();
+    if lower == "title" {
+        return Some(1);
+    }
+    lower
+        .strip_prefix("heading")
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|n| (1..=9).contains(n))
+}
+
+/// How one indent level of a list is numbered.
+#[derive(Clone, Debug, Default)]
+struct ListLevel {
+    /// `decimal`, `lowerLetter`, `bullet` and so on.
+    format: String,
+    /// The first number, which Word takes to be 0 when none is given.
+    start: usize,
+}
+
+/// Reads `numbering.xml` into the levels of every numbering id.
+fn list_levels(numbering: &str) -> HashMap<String, HashMap<u8, ListLevel>> {
+    let mut abstract_levels: HashMap<String, HashMap<u8, ListLevel>> = HashMap::new();
+    let mut abstract_ids: HashMap<String, String> = HashMap::new();
+    let mut abstract_id: Option<String> = None;
+    let mut num_id: Option<String> = None;
+    let mut level: Option<u8> = None;
+
+    for event in EventReader::from_str(numbering).into_iter().flatten() {
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |key: &str| attribute(&attributes, key);
+                match name.local_name.as_str() {
+                    "abstractNum" => abstract_id = attr("abstractNumId"),
+                    "num" => num_id = attr("numId"),
+                    "abstractNumId" => {
+                        if let (Some(num_id), Some(id)) = (&num_id, attr("val")) {
+                            abstract_ids.insert(num_id.clone(), id);
+                        }
+                    }
+                    "lvl" => level = attr("ilvl").and_then(|l| l.parse().ok()),
+                    "numFmt" | "start" => {
+                        if let (Some(id), Some(level), Some(value)) = (&abstract_id, level, attr("val")) {
+                            let entry = abstract_levels.entry(id.clone()).or_default().entry(level).or_default();
+                            if name.local_name == "numFmt" {
+                                entry.format = value;
+                            } else {
+                                entry.start = value.parse().unwrap_or_default();
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "abstractNum" => abstract_id = None,
+                "num" => num_id = None,
+                "lvl" => level = None,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    abstract_ids
+        .into_iter()
+        .filter_map(|(num_id, abstract_id)| Some((num_id, abstract_levels.get(&abstract_id)?.clone())))
+        .collect()
+}
+
+/// Counts one more item at `level` of list `id` and returns its number as
+/// Word shows it (`3.`, `b.`, `iv.`), or `None` for bullets. Starting an
+/// item restarts the numbering of the levels below it.
+fn list_marker(
+    lists: &HashMap<String, HashMap<u8, ListLevel>>,
+    counters: &mut HashMap<(String, u8), usize>,
+    id: &str,
+    level: u8,
+) -> Option<String> {
+    let list_level = lists.get(id)?.get(&level)?;
+    counters.retain(|(counter_id, counter_level), _| counter_id != id || *counter_level <= level);
+    // Items counted so far, so a list may start at 0.
+    let counter = counters.entry((id.to_string(), level)).or_insert(0);
+    let number = list_level.start + *counter;
+    *counter += 1;
+
+    let letter = |base: u8| {
+        let letter = (base + ((number - 1) % 26) as u8) as char;
+        letter.to_string().repeat((number - 1) / 26 + 1)
+    };
+    let marker = match list_level.format.as_str() {
+        "decimal" | "decimalZero" => number.to_string(),
+        // There is no letter or numeral for zero.
+        "lowerLetter" | "upperLetter" | "lowerRoman" | "upperRoman" if number == 0 => number.to_string(),
+        "lowerLetter" => letter(b'a'),
+        "upperLetter" => letter(b'A'),
+        "lowerRoman" => roman(number).to_lowercase(),
+        "upperRoman" => roman(number),
+        _ => return None,
+    };
+    Some(format!("{}.", marker))
+}
+
+fn roman(mut number: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    let mut roman = String::new();
+    for &(value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(format: &str, start: usize) -> HashMap<String, HashMap<u8, ListLevel>> {
+        let level = ListLevel { format: format.to_string(), start };
+        HashMap::from([("1".to_string(), HashMap::from([(0, level)]))])
+    }
+
+    fn markers(lists: &HashMap<String, HashMap<u8, ListLevel>>, count: usize) -> Vec<Option<String>> {
+        let mut counters = HashMap::new();
+        (0..count).map(|_| list_marker(lists, &mut counters, "1", 0)).collect()
+    }
+
+    #[test]
+    fn numbers_lists_from_their_start() {
+        let some = |marker: &str| Some(marker.to_string());
+        assert_eq!(markers(&list("decimal", 1), 3), [some("1."), some("2."), some("3.")]);
+        assert_eq!(markers(&list("decimal", 0), 3), [some("0."), some("1."), some("2.")]);
+        assert_eq!(markers(&list("lowerLetter", 26), 2), [some("z."), some("aa.")]);
+        assert_eq!(markers(&list("upperRoman", 3), 2), [some("III."), some("IV.")]);
+        assert_eq!(markers(&list("bullet", 1), 1), [None]);
+    }
+
+    #[test]
+    fn a_level_without_a_usable_start_counts_from_zero() {
+        let xml = r#"<w:numbering xmlns:w="w">
+            <w:abstractNum w:abstractNumId="0">
+                <w:lvl w:ilvl="0"><w:numFmt w:val="decimal"/></w:lvl>
+                <w:lvl w:ilvl="1"><w:start w:val="x"/><w:numFmt w:val="decimal"/></w:lvl>
+            </w:abstractNum>
+            <w:num w:numId="1"><w:abstractNumId w:val="0"/></w:num>
+        </w:numbering>"#;
+        let lists = list_levels(xml);
+        assert_eq!(lists["1"][&0].start, 0);
+        assert_eq!(lists["1"][&1].start, 0);
+    }
+
+    #[test]
+    fn bold_and_italic_are_tracked_separately() {
+        let xml = r#"<w:document xmlns:w="w"><w:body><w:p>
+            <w:r><w:rPr><w:b/><w:i w:val="0"/></w:rPr><w:t>bold</w:t></w:r>
+            <w:r><w:rPr><w:i/></w:rPr><w:t>italic</w:t></w:r>
+            <w:r><w:rPr><w:b w:val="false"/></w:rPr><w:t>plain</w:t></w:r>
+        </w:p></w:body></w:document>"#;
+        let paragraphs = parse_paragraphs(xml, "test.docx").unwrap();
+        let runs: Vec<(&str, bool)> = paragraphs[0]
+            .runs
+            .iter()
+            .filter_map(|run| match run {
+                Run::Text(text, emphasis) => Some((text.as_str(), *emphasis)),
+                Run::Note(..) => None,
+            })
+            .collect();
+        assert_eq!(runs, [("bold", true), ("italic", true), ("plain", false)]);
+    }
+}
@@ -0,0 +1,320 @@
+use ::epub::doc::{EpubDoc, NavPoint};
+use scraper::{ElementRef, Html, Selector};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Component, Path, PathBuf};
+use xml::reader::{EventReader, XmlEvent};
+
+use super::markup::{first_heading, push_html};
+use super::{attribute, ExtractError, Progress};
+use crate::document::{Document, DocumentBuilder};
+
+/// A table-of-contents entry resolved to the archive path it points at.
+struct TocEntry {
+    label: String,
+    path: PathBuf,
+}
+
+/// Reads an EPUB by walking its spine and converting every XHTML document
+/// to plain text. Each chapter becomes a section titled from the NCX table
+/// of contents, or from the EPUB 3 navigation document when there is no NCX.
+/// Reports progress in spine entries.
+pub fn extract_epub(file_path: &str, progress: &Progress) -> Result<Document, ExtractError> {
+    let mut doc = EpubDoc::new(file_path).map_err(|e| match e.downcast::<io::Error>() {
+        Ok(e) => ExtractError::io(file_path, e),
+        Err(e) => ExtractError::corrupt(file_path, format!("not a valid EPUB: {}", e)),
+    })?;
+
+    let mut toc = Vec::new();
+    flatten_nav_points(&doc.toc, &mut toc);
+    if toc.is_empty() {
+        toc = nav_document_toc(&mut doc);
+    }
+
+    let mut builder = DocumentBuilder::new();
+    builder.set_title(doc.mdata("title"));
+    builder.set_identifier(doc.mdata("identifier"));
+    let mut has_chapter = false;
+
+    let spine = doc.spine.clone();
+    for (done, id) in spine.iter().enumerate() {
+        progress.update(file_path, done, spine.len())?;
+        let Some((path, mime)) = doc.resources.get(id).cloned() else {
+            continue;
+        };
+        if !mime.contains("html") {
+            continue;
+        }
+        // A single unreadable entry should not cost the reader the whole book.
+        let Ok(content) = doc.get_resource_str(id) else {
+            continue;
+        };
+
+        let html = Html::parse_document(&content);
+        let path = normalize_path(&path);
+        let toc_title = toc.iter().find(|entry| entry.path == path).map(|entry| entry.label.clone());
+
+        // Spine items without a TOC entry continue the previous chapter
+        // (books often split one chapter over several files).
+        if toc_title.is_some() || !has_chapter || toc.is_empty() {
+            builder.start_section(toc_title.or_else(|| first_heading(&html)), 1);
+            has_chapter = true;
+        }
+        push_html(&html, &mut builder, false);
+    }
+
+    let document = builder.finish();
+    if document.is_empty() {
+        return Err(ExtractError::Empty { path: file_path.to_string() });
+    }
+
+    Ok(document)
+}
+
+fn flatten_nav_points(points: &[NavPoint], entries: &mut Vec<TocEntry>) {
+    for point in points {
+        entries.push(TocEntry {
+            label: point.label.trim().to_string(),
+            path: strip_fragment(&point.content),
+        });
+        flatten_nav_points(&point.children, entries);
+    }
+}
+
+/// Reads the EPUB 3 `nav` document declared in the package manifest.
+fn nav_document_toc(doc: &mut EpubDoc<BufReader<File>>) -> Vec<TocEntry> {
+    let Ok(package) = doc.get_resource_str_by_path(doc.root_file.clone()) else {
+        return Vec::new();
+    };
+
+    let nav_href = EventReader::from_str(&package)
+        .into_iter()
+        .filter_map(Result::ok)
+        .find_map(|event| match event {
+            XmlEvent::StartElement { name, attributes, .. } if name.local_name == "item" => {
+                let attr = |key: &str| attribute(&attributes, key);
+                let is_nav = attr("properties")
+                    .map(|p| p.split_whitespace().any(|p| p == "nav"))
+                    .unwrap_or(false);
+                if is_nav { attr("href") } else { None }
+            }
+            _ => None,
+        });
+
+    let Some(nav_href) = nav_href else {
+        return Vec::new();
+    };
+    let nav_path = doc.root_base.join(&nav_href);
+    let Ok(nav_html) = doc.get_resource_str_by_path(&nav_path) else {
+        return Vec::new();
+    };
+    let nav_dir = nav_path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let document = Html::parse_document(&nav_html);
+    let nav_selector = Selector::parse("nav").unwrap();
+    let link_selector = Selector::parse("a[href]").unwrap();
+
+    let navs: Vec<ElementRef> = document.select(&nav_selector).collect();
+    let toc_nav = navs
+        .iter()
+        .find(|nav| nav.value().attr("epub:type").map(|t| t.contains("toc")).unwrap_or(false))
+        .or_else(|| navs.first());
+
+    let Some(toc_nav) = toc_nav else {
+        return Vec::new();
+    };
+
+    toc_nav
+        .select(&link_selector)
+        .filter_map(|link| {
+            let label = link.text().collect::<Vec<_>>().join(" ");
+            let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+            let href = link.value().attr("href")?;
+            if label.is_empty() {
+                return None;
+            }
+            Some(TocEntry {
+                label,
+                path: strip_fragment(&nav_dir.join(href)),
+            })
+        })
+        .collect()
+}
+
+/// Drops a `#fragment` from a TOC target and normalises the path.
+fn strip_fragment(path: &Path) -> PathBuf {
+    let path = path.to_string_lossy();
+    let without_fragment = path.split('#').next().unwrap_or_default();
+    normalize_path(Path::new(without_fragment))
+}
+
+/// Resolves `.` and `..` so TOC targets compare equal to manifest paths.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    const CONTAINER: &str = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+
+    const NCX: &str = r#"<?xml version="1.0"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <navMap>
+    <navPoint id="n1" playOrder="1"><navLabel><text>Loomings</text></navLabel><content src="one.xhtml"/></navPoint>
+    <navPoint id="n2" playOrder="2"><navLabel><text>The Carpet-Bag</text></navLabel><content src="two.xhtml#start"/></navPoint>
+  </navMap>
+</ncx>"#;
+
+    const NAV: &str = r#"<?xml version="1.0"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+  <nav epub:type="toc"><ol>
+    <li><a href="one.xhtml">Loomings</a></li>
+    <li><a href="two.xhtml">The Carpet-Bag</a></li>
+  </ol></nav>
+</body></html>"#;
+
+    fn chapter(heading: &str, text: &str) -> String {
+        format!(
+            r#"<?xml version="1.0"?><html xmlns="http://www.w3.org/1999/xhtml"><body><h2>{}</h2><p>{}</p></body></html>"#,
+            heading, text
+        )
+    }
+
+    /// A package whose spine lists `two` before `one`, with a TOC from
+    /// `toc.ncx` or `nav.xhtml` depending on `toc`.
+    fn package(toc: &str) -> String {
+        let (item, spine) = match toc {
+            "ncx" => (r#"<item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#, r#"<spine toc="ncx">"#),
+            _ => (r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#, "<spine>"),
+        };
+        format!(
+            r#"<?xml version="1.0"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Moby-Dick</dc:title><dc:identifier id="id">urn:isbn:9780142437247</dc:identifier>
+  </metadata>
+  <manifest>
+    {}
+    <item id="one" href="one.xhtml" media-type="application/xhtml+xml"/>
+    <item id="two" href="two.xhtml" media-type="application/xhtml+xml"/>
+    <item id="three" href="three.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  {}<itemref idref="two"/><itemref idref="one"/><itemref idref="three"/></spine>
+</package>"#,
+            item, spine
+        )
+    }
+
+    /// Writes a ZIP of `files` to a file of its own and returns its path.
+    fn archive(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rsvp-epub-{}-{}.epub", std::process::id(), name));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    fn book(name: &str, toc: &str) -> PathBuf {
+        let one = chapter("Chapter 1", "Call me Ishmael.");
+        let two = chapter("Chapter 2", "I stuffed a shirt or two into my old carpet-bag.");
+        let three = chapter("Chapter 3", "Entering that gable-ended Spouter-Inn.");
+        archive(
+            name,
+            &[
+                ("mimetype", "application/epub+zip"),
+                ("META-INF/container.xml", CONTAINER),
+                ("OEBPS/content.opf", &package(toc)),
+                ("OEBPS/toc.ncx", NCX),
+                ("OEBPS/nav.xhtml", NAV),
+                ("OEBPS/one.xhtml", &one),
+                ("OEBPS/two.xhtml", &two),
+                ("OEBPS/three.xhtml", &three),
+            ],
+        )
+    }
+
+    fn sections(document: &Document) -> Vec<(Option<&str>, String)> {
+        document
+            .sections
+            .iter()
+            .map(|section| (section.title.as_deref(), document.join(section.tokens.clone())))
+            .collect()
+    }
+
+    #[test]
+    fn reads_chapters_in_spine_order_titled_from_the_ncx() {
+        let path = book("ncx", "ncx");
+        let document = extract_epub(path.to_str().unwrap(), &Progress::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(document.title.as_deref(), Some("Moby-Dick"));
+        assert_eq!(document.identifier.as_deref(), Some("urn:isbn:9780142437247"));
+        assert_eq!(
+            sections(&document),
+            [
+                (
+                    Some("The Carpet-Bag"),
+                    "Chapter 2 I stuffed a shirt or two into my old carpet-bag.".to_string()
+                ),
+                (
+                    Some("Loomings"),
+                    "Chapter 1 Call me Ishmael. Chapter 3 Entering that gable-ended Spouter-Inn.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_chapter_titles_from_the_nav_document_without_an_ncx() {
+        let path = book("nav", "nav");
+        let document = extract_epub(path.to_str().unwrap(), &Progress::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let titles: Vec<_> = document.sections.iter().map(|section| section.title.as_deref()).collect();
+        assert_eq!(titles, [Some("The Carpet-Bag"), Some("Loomings")]);
+    }
+
+    #[test]
+    fn an_archive_without_a_container_is_an_error() {
+        let path = archive("no-container", &[("mimetype", "application/epub+zip"), ("OEBPS/one.xhtml", "<p>Hi</p>")]);
+        let result = extract_epub(path.to_str().unwrap(), &Progress::default());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ExtractError::CorruptArchive { .. })), "{:?}", result.err());
+    }
+
+    #[test]
+    fn a_file_that_is_not_a_zip_is_an_error() {
+        let path = std::env::temp_dir().join(format!("rsvp-epub-{}-garbage.epub", std::process::id()));
+        fs::write(&path, "this is not a zip archive").unwrap();
+        let result = extract_epub(path.to_str().unwrap(), &Progress::default());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ExtractError::CorruptArchive { .. })), "{:?}", result.err());
+    }
+
+    #[test]
+    fn a_missing_file_is_an_io_error() {
+        let result = extract_epub("/nonexistent/book.epub", &Progress::default());
+        assert!(matches!(result, Err(ExtractError::Io { .. })), "{:?}", result.err());
+    }
+}
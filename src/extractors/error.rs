@@ -0,0 +1,75 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use zip::result::ZipError;
+
+/// Why a file or web page could not be turned into a document.
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The file could not be opened or read.
+    Io { path: String, source: io::Error },
+    /// The file is not in a format rsvp can read.
+    UnsupportedFormat { path: String },
+    /// The file's container or markup is damaged: a broken ZIP, EPUB or PDF,
+    /// or malformed XML inside one.
+    CorruptArchive { path: String, reason: String },
+    /// The file is binary rather than text, or its text is not in the
+    /// encoding it should be.
+    Encoding { path: String },
+    /// The encoding asked for in the options is not one rsvp knows.
+    UnknownEncoding { label: String },
+    /// A web page could not be fetched.
+    Network { url: String, source: reqwest::Error },
+    /// The file was read but has no text in it.
+    Empty { path: String },
+    /// Loading was stopped with [`Progress::cancel`](super::Progress::cancel).
+    Cancelled { path: String },
+}
+
+impl ExtractError {
+    /// Wraps an I/O error, reporting invalid UTF-8 as an encoding problem.
+    pub fn io(path: &str, source: io::Error) -> ExtractError {
+        if source.kind() == io::ErrorKind::InvalidData {
+            ExtractError::Encoding { path: path.to_string() }
+        } else {
+            ExtractError::Io { path: path.to_string(), source }
+        }
+    }
+
+    pub fn corrupt(path: &str, reason: impl fmt::Display) -> ExtractError {
+        ExtractError::CorruptArchive { path: path.to_string(), reason: reason.to_string() }
+    }
+
+    /// Wraps an error from reading a ZIP-based format (`format` is e.g. "DOCX").
+    pub fn zip(path: &str, format: &str, error: ZipError) -> ExtractError {
+        match error {
+            ZipError::Io(source) => ExtractError::io(path, source),
+            error => ExtractError::corrupt(path, format!("not a valid {} archive: {}", format, error)),
+        }
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::Io { path, source } => write!(f, "Failed to read '{}': {}", path, source),
+            ExtractError::UnsupportedFormat { path } => write!(f, "'{}' is not a file type rsvp can read", path),
+            ExtractError::CorruptArchive { path, reason } => write!(f, "'{}' is damaged: {}", path, reason),
+            ExtractError::Encoding { path } => write!(f, "'{}' is not text rsvp can decode", path),
+            ExtractError::UnknownEncoding { label } => write!(f, "Unknown encoding '{}'", label),
+            ExtractError::Network { url, source } => write!(f, "Failed to fetch '{}': {}", url, source),
+            ExtractError::Empty { path } => write!(f, "No readable text was found in '{}'", path),
+            ExtractError::Cancelled { path } => write!(f, "Loading '{}' was cancelled", path),
+        }
+    }
+}
+
+impl Error for ExtractError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExtractError::Io { source, .. } => Some(source),
+            ExtractError::Network { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag};
+
+use crate::document::{Document, DocumentBuilder};
+
+/// Builds a document from Markdown; every heading opens a section.
+pub fn markdown_to_document(content: &str) -> Document {
+    let mut builder = DocumentBuilder::new();
+    let mut heading: Option<(u8, String)> = None;
+    let mut emphasis_depth = 0usize;
+
+    for event in Parser::new_ext(content, Options::all()) {
+        match event {
+            Event::Start(Tag::Heading(level, ..)) => {
+                heading = Some((heading_level(level), String::new()));
+            }
+            Event::End(Tag::Heading(..)) => {
+                if let Some((level, text)) = heading.take() {
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !text.is_empty() {
+                        builder.start_section(Some(text.clone()), level);
+                        builder.start_heading(level);
+                        builder.push_text(&text);
+                        builder.end_paragraph();
+                    }
+                }
+            }
+            Event::Start(Tag::Emphasis) | Event::Start(Tag::Strong) => {
+                emphasis_depth += 1;
+                builder.set_emphasis(true);
+            }
+            Event::End(Tag::Emphasis) | Event::End(Tag::Strong) => {
+                emphasis_depth = emphasis_depth.saturating_sub(1);
+                builder.set_emphasis(emphasis_depth > 0);
+            }
+            Event::End(Tag::Paragraph)
+            | Event::End(Tag::Item)
+            | Event::End(Tag::CodeBlock(_))
+            | Event::End(Tag::TableRow)
+            | Event::End(Tag::TableHead)
+            | Event::Rule => builder.end_paragraph(),
+            Event::Text(text) | Event::Code(text) => match heading.as_mut() {
+                Some((_, heading_text)) => heading_text.push_str(&text),
+                None => builder.push_text(&text),
+            },
+            Event::SoftBreak | Event::HardBreak => match heading.as_mut() {
+                Some((_, heading_text)) => heading_text.push(' '),
+                None => builder.push_text(" "),
+            },
+            Event::End(Tag::TableCell) => builder.push_text(" "),
+            _ => {}
+        }
+    }
+
+    builder.finish()
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
@@ -0,0 +1,179 @@
+use scraper::{ElementRef, Html, Node, Selector};
+
+use super::article::{is_boilerplate, main_content, TextLengths};
+use super::HtmlMode;
+use crate::document::{Document, DocumentBuilder};
+
+/// Elements whose text is never shown to the reader.
+pub(super) const SKIPPED_ELEMENTS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "math", "object", "iframe",
+];
+
+/// Elements that start a new paragraph when they open or close.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "br", "caption", "dd", "div", "dl",
+    "dt", "figcaption", "figure", "footer", "header", "hr", "li", "main", "nav", "ol", "p",
+    "pre", "section", "table", "td", "th", "tr", "ul",
+];
+
+/// Inline elements rendered as emphasis.
+const EMPHASIS_ELEMENTS: &[&str] = &["b", "cite", "em", "i", "strong"];
+
+/// Builds a document from an HTML page; every `h1`-`h6` opens a section.
+///
+/// With [`HtmlMode::Article`] only the page's main article is read, under
+/// its headline, and navigation, sidebars, banners and footers are left
+/// out. A page without a clear article is read whole, still without them.
+pub fn html_to_document(html: &str, mode: HtmlMode) -> Document {
+    let document = Html::parse_document(html);
+    let mut builder = DocumentBuilder::new();
+    builder.set_title(page_title(&document));
+
+    // Only needed to tell the article from the page furniture.
+    let lengths = (mode == HtmlMode::Article).then(|| TextLengths::new(&document));
+    let clean = lengths.as_ref();
+    match clean.and_then(|lengths| main_content(&document, lengths)) {
+        Some(elements) => {
+            // The headline often sits above the article, next to the byline.
+            let headline = clean
+                .and_then(|lengths| headline(&document, lengths))
+                .or_else(|| page_title(&document));
+            if let Some(headline) = headline.filter(|headline| !has_heading(&elements, headline)) {
+                builder.start_section(Some(headline.clone()), 1);
+                builder.start_heading(1);
+                builder.push_text(&headline);
+                builder.end_paragraph();
+            }
+            for element in elements {
+                push_element(element, &mut builder, true, clean, &mut 0);
+            }
+        }
+        None => push_element(document.root_element(), &mut builder, true, clean, &mut 0),
+    }
+    builder.finish()
+}
+
+/// Appends the text of an (X)HTML document to `builder`, dropping all markup.
+///
+/// When `headings_start_sections` is false, headings are only flagged on
+/// their tokens; callers that already know the chapter structure (EPUB)
+/// open sections themselves.
+pub fn push_html(document: &Html, builder: &mut DocumentBuilder, headings_start_sections: bool) {
+    push_element(document.root_element(), builder, headings_start_sections, None, &mut 0);
+    builder.end_paragraph();
+}
+
+/// Returns the text of the first `h1`-`h3` heading, if the document has one.
+pub fn first_heading(document: &Html) -> Option<String> {
+    let heading = document
+        .root_element()
+        .descendent_elements()
+        .find(|e| matches!(e.value().name(), "h1" | "h2" | "h3"))
+        .map(element_text)?;
+    (!heading.is_empty()).then_some(heading)
+}
+
+fn page_title(document: &Html) -> Option<String> {
+    let selector = Selector::parse("title").unwrap();
+    let title = document.select(&selector).next().map(element_text)?;
+    (!title.is_empty()).then_some(title)
+}
+
+/// The first `h1` outside the page's navigation, sidebars and footers.
+fn headline(document: &Html, lengths: &TextLengths) -> Option<String> {
+    let selector = Selector::parse("body h1").unwrap();
+    let headline = document
+        .select(&selector)
+        .find(|heading| {
+            !heading
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| is_boilerplate(ancestor, lengths))
+        })
+        .map(element_text)?;
+    (!headline.is_empty()).then_some(headline)
+}
+
+/// Whether `elements` already contain a heading reading `text`.
+fn has_heading(elements: &[ElementRef], text: &str) -> bool {
+    elements.iter().any(|element| {
+        std::iter::once(*element)
+            .chain(element.descendent_elements())
+            .any(|e| heading_level(e.value().name()).is_some() && element_text(e) == text)
+    })
+}
+
+fn element_text(element: ElementRef) -> String {
+    let text = element.text().collect::<Vec<_>>().join(" ");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn heading_level(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Appends `element` to `builder`; when `clean` is given the page's text
+/// lengths, page furniture such as navigation and footers is left out.
+fn push_element(
+    element: ElementRef,
+    builder: &mut DocumentBuilder,
+    headings_start_sections: bool,
+    clean: Option<&TextLengths>,
+    emphasis_depth: &mut usize,
+) {
+    let name = element.value().name();
+    if SKIPPED_ELEMENTS.contains(&name) || clean.is_some_and(|lengths| is_boilerplate(element, lengths)) {
+        return;
+    }
+
+    if let Some(level) = heading_level(name) {
+        let text = element_text(element);
+        if !text.is_empty() {
+            if headings_start_sections {
+                builder.start_section(Some(text.clone()), level);
+            }
+            builder.start_heading(level);
+            builder.push_text(&text);
+            builder.end_paragraph();
+        }
+        return;
+    }
+
+    let is_block = BLOCK_ELEMENTS.contains(&name);
+    let is_emphasis = EMPHASIS_ELEMENTS.contains(&name);
+    if is_block {
+        builder.end_paragraph();
+    }
+    if is_emphasis {
+        *emphasis_depth += 1;
+        builder.set_emphasis(true);
+    }
+
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => builder.push_text(text),
+            Node::Element(_) => {
+                if let Some(child_element) = ElementRef::wrap(child) {
+                    push_element(child_element, builder, headings_start_sections, clean, emphasis_depth);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if is_emphasis {
+        *emphasis_depth -= 1;
+        builder.set_emphasis(*emphasis_depth > 0);
+    }
+    if is_block {
+        builder.end_paragraph();
+    }
+}
@@ -0,0 +1,62 @@
+//! Format-specific readers that turn files into a [`Document`](crate::document::Document).
+
+use std::fs::File;
+use std::io::Read;
+use xml::attribute::OwnedAttribute;
+use zip::read::ZipArchive;
+
+mod error;
+mod options;
+mod progress;
+
+pub use error::ExtractError;
+pub use options::{ExtractOptions, HtmlMode, NoteMode};
+pub use progress::Progress;
+
+/// Bump whenever an extractor's output changes, so cached documents made by
+/// the old version are not reused.
+pub const VERSION: u32 = 10;
+
+pub mod article;
+pub mod docx;
+pub mod epub;
+pub mod markdown;
+pub mod markup;
+pub mod odt;
+pub mod pdf;
+pub mod text;
+
+/// Reads the part `name` of a ZIP-based format such as DOCX or ODT as
+/// text, or `None` when the archive has no such part.
+fn read_entry(archive: &mut ZipArchive<File>, file_path: &str, name: &str) -> Result<Option<String>, ExtractError> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(None),
+    };
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .map_err(|e| ExtractError::corrupt(file_path, format!("failed to read {}: {}", name, e)))?;
+    Ok(Some(content))
+}
+
+/// The value of the XML attribute called `name`, whatever its namespace.
+fn attribute(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.clone())
+}
+
+/// A paragraph of a word processor document, built run by run.
+trait Runs {
+    fn push_run(&mut self, text: String, emphasis: bool);
+}
+
+/// Ends the run collected in `text`, adding it to `paragraph`.
+fn flush(text: &mut String, emphasis: bool, paragraph: Option<&mut impl Runs>) {
+    let run = std::mem::take(text);
+    if let Some(paragraph) = paragraph.filter(|_| !run.is_empty()) {
+        paragraph.push_run(run, emphasis);
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+use std::fs::File;
+use xml::name::OwnedName;
+use xml::reader::{EventReader, XmlEvent};
+use zip::read::ZipArchive;
+
+use super::{attribute, flush, read_entry, ExtractError, ExtractOptions, NoteMode, Runs};
+use crate::document::{Document, DocumentBuilder};
+
+const OFFICE_NS: &str = "urn:oasis:names:tc:opendocument:xmlns:office:1.0";
+const TEXT_NS: &str = "urn:oasis:names:tc:opendocument:xmlns:text:1.0";
+const SVG_NS: &str = "urn:oasis:names:tc:opendocument:xmlns:svg-compatible:1.0";
+
+/// Reads an OpenDocument text file. Every `text:h` opens a section at its
+/// outline level, spans are joined without adding spaces, and comments and
+/// tracked deletions are left out. Footnotes and endnotes are read after
+/// the paragraph that cites them, or skipped, as `options` ask.
+pub fn extract_odt(file_path: &str, options: &ExtractOptions) -> Result<Document, ExtractError> {
+    let file = File::open(file_path).map_err(|e| ExtractError::io(file_path, e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| ExtractError::zip(file_path, "ODT", e))?;
+
+    let content = read_entry(&mut archive, file_path, "content.xml")?
+        .ok_or_else(|| ExtractError::corrupt(file_path, "ODT has no content.xml"))?;
+    let mut emphasis = HashSet::new();
+    if let Some(styles) = read_entry(&mut archive, file_path, "styles.xml")? {
+        emphasis_styles(&styles, &mut emphasis);
+    }
+    emphasis_styles(&content, &mut emphasis);
+
+    let mut builder = DocumentBuilder::new();
+    builder.set_title(read_entry(&mut archive, file_path, "meta.xml")?.and_then(|meta| title(&meta)));
+
+    for paragraph in parse_paragraphs(&content, file_path, options, &emphasis)? {
+        if let Some(level) = paragraph.level {
+            let title = paragraph.title();
+            if !title.is_empty() {
+                builder.start_section(Some(title), level);
+                builder.start_heading(level);
+            }
+        }
+        paragraph.push_to(&mut builder);
+
+        for note in &paragraph.notes {
+            for (i, note_paragraph) in note.paragraphs.iter().enumerate() {
+                if i == 0 {
+                    builder.push_text(&format!("[{}] ", note.citation.trim()));
+                }
+                note_paragraph.push_to(&mut builder);
+            }
+        }
+    }
+
+    let document = builder.finish();
+    if document.is_empty() {
+        return Err(ExtractError::Empty { path: file_path.to_string() });
+    }
+    Ok(document)
+}
+
+/// A paragraph or heading from `content.xml`.
+#[derive(Default)]
+struct OdtParagraph {
+    /// Outline level, for headings.
+    level: Option<u8>,
+    /// Runs of text and whether each is bold or italic.
+    runs: Vec<(String, bool)>,
+    /// Notes cited in the paragraph, in order.
+    notes: Vec<Note>,
+    /// How many notes the paragraph is inside of.
+    note_depth: usize,
+}
+
+impl Runs for OdtParagraph {
+    fn push_run(&mut self, text: String, emphasis: bool) {
+        self.runs.push((text, emphasis));
+    }
+}
+
+impl OdtParagraph {
+    /// The paragraph's text with whitespace collapsed and without the marks
+    /// of the notes it cites, for use as a section title.
+    fn title(&self) -> String {
+        let text: String = self
+            .runs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !self.notes.iter().any(|note| note.run == *i))
+            .map(|(_, (run, _))| run.as_str())
+            .collect();
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn push_to(&self, builder: &mut DocumentBuilder) {
+        for (run, emphasis) in &self.runs {
+            builder.set_emphasis(*emphasis);
+            builder.push_text(run);
+        }
+        builder.set_emphasis(false);
+        builder.end_paragraph();
+    }
+}
+
+/// A footnote or endnote: the mark shown in the text and what it says.
+#[derive(Default)]
+struct Note {
+    citation: String,
+    /// The run of the citing paragraph that shows the mark.
+    run: usize,
+    paragraphs: Vec<OdtParagraph>,
+}
+
+/// Splits `content.xml` into paragraphs, rebuilding the text as it is shown:
+/// `text:s` and `text:tab` become spaces and line breaks separate words.
+fn parse_paragraphs(
+    content: &str,
+    file_path: &str,
+    options: &ExtractOptions,
+    emphasis_styles: &HashSet<String>,
+) -> Result<Vec<OdtParagraph>, ExtractError> {
+    let mut paragraphs = Vec::new();
+    // Frames and notes put paragraphs inside paragraphs.
+    let mut open: Vec<OdtParagraph> = Vec::new();
+    let mut notes: Vec<Note> = Vec::new();
+    let mut text = String::new();
+    // Whether each open span is bold or italic.
+    let mut spans: Vec<bool> = Vec::new();
+    let mut in_citation = false;
+    let mut skip_depth = 0;
+
+    for event in EventReader::from_str(content) {
+        let event = event.map_err(|e| ExtractError::corrupt(file_path, format!("malformed ODT XML: {}", e)))?;
+        let emphasis = spans.iter().any(|&span| span);
+
+        if skip_depth > 0 {
+            match &event {
+                XmlEvent::StartElement { .. } => skip_depth += 1,
+                XmlEvent::EndElement { .. } => skip_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |key: &str| attribute(&attributes, key);
+                if is_skipped(&name) || (name.local_name == "note" && options.notes == NoteMode::Skip) {
+                    skip_depth = 1;
+                    continue;
+                }
+                match name.local_name.as_str() {
+                    "p" | "h" => {
+                        flush(&mut text, emphasis, open.last_mut());
+                        let level = (name.local_name == "h")
+                            .then(|| attr("outline-level").and_then(|l| l.parse().ok()).unwrap_or(1));
+                        open.push(OdtParagraph { level, note_depth: notes.len(), ..OdtParagraph::default() });
+                    }
+                    "span" => {
+                        flush(&mut text, emphasis, open.last_mut());
+                        spans.push(attr("style-name").is_some_and(|style| emphasis_styles.contains(&style)));
+                    }
+                    "s" => text.push_str(&" ".repeat(attr("c").and_then(|c| c.parse().ok()).unwrap_or(1))),
+                    "tab" | "line-break" => text.push(' '),
+                    "note" => {
+                        flush(&mut text, emphasis, open.last_mut());
+                        notes.push(Note::default());
+                    }
+                    "note-citation" => in_citation = true,
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(chars) | XmlEvent::Whitespace(chars) => {
+                if in_citation {
+                    if let Some(note) = notes.last_mut() {
+                        note.citation.push_str(&chars);
+                    }
+                } else if !open.is_empty() {
+                    text.push_str(&chars);
+                }
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "p" | "h" => {
+                    flush(&mut text, emphasis, open.last_mut());
+                    let Some(paragraph) = open.pop() else {
+                        continue;
+                    };
+                    match notes.last_mut() {
+                        Some(note) if paragraph.note_depth > 0 => note.paragraphs.push(paragraph),
+                        _ => paragraphs.push(paragraph),
+                    }
+                }
+                "span" => {
+                    flush(&mut text, emphasis, open.last_mut());
+                    spans.pop();
+                }
+                "note-citation" => in_citation = false,
+                "note" => {
+                    let Some(note) = notes.pop() else {
+                        continue;
+                    };
+                    if let Some(paragraph) = open.last_mut() {
+                        paragraph.runs.push((format!("[{}]", note.citation.trim()), false));
+                        paragraph.notes.push(Note { run: paragraph.runs.len() - 1, ..note });
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    Ok(paragraphs)
+}
+
+/// Elements whose text is not part of what the reader sees: comments,
+/// tracked deletions, declarations and the descriptions of images.
+fn is_skipped(name: &OwnedName) -> bool {
+    match name.namespace.as_deref() {
+        Some(OFFICE_NS) => name.local_name == "annotation",
+        Some(TEXT_NS) => matches!(
+            name.local_name.as_str(),
+            "tracked-changes" | "sequence-decls" | "variable-decls" | "user-field-decls" | "alphabetical-index-mark"
+        ),
+        Some(SVG_NS) => matches!(name.local_name.as_str(), "title" | "desc"),
+        _ => false,
+    }
+}
+
+/// Adds the names of text styles that are italic or bold to `styles`.
+fn emphasis_styles(xml: &str, styles: &mut HashSet<String>) {
+    let mut current: Option<String> = None;
+
+    for event in EventReader::from_str(xml).into_iter().flatten() {
+        match event {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attr = |key: &str| attribute(&attributes, key);
+                match name.local_name.as_str() {
+                    "style" => current = attr("name"),
+                    "text-properties" => {
+                        let italic = matches!(attr("font-style").as_deref(), Some("italic" | "oblique"));
+                        let bold = matches!(attr("font-weight").as_deref(), Some("bold" | "600" | "700" | "800" | "900"));
+                        if let Some(style) = current.as_ref().filter(|_| italic || bold) {
+                            styles.insert(style.clone());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } if name.local_name == "style" => current = None,
+            _ => {}
+        }
+    }
+}
+
+/// The `dc:title` from `meta.xml`.
+fn title(meta: &str) -> Option<String> {
+    let mut in_title = false;
+    let mut title = String::new();
+    for event in EventReader::from_str(meta).into_iter().flatten() {
+        match event {
+            XmlEvent::StartElement { name, .. } => in_title = name.local_name == "title",
+            XmlEvent::Characters(chars) if in_title => title.push_str(&chars),
+            XmlEvent::EndElement { .. } => in_title = false,
+            _ => {}
+        }
+    }
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(body: &str) -> String {
+        format!(
+            r#"<office:document-content xmlns:office="{}" xmlns:t="{}" xmlns:svg="{}"><office:body><office:text>{}</office:text></office:body></office:document-content>"#,
+            OFFICE_NS, TEXT_NS, SVG_NS, body
+        )
+    }
+
+    fn parse(body: &str) -> Vec<OdtParagraph> {
+        parse_paragraphs(&content(body), "test.odt", &ExtractOptions::default(), &HashSet::new()).unwrap()
+    }
+
+    #[test]
+    fn skips_comments_and_deletions_whatever_their_prefix() {
+        let paragraphs = parse(
+            r#"<t:tracked-changes><t:p>deleted</t:p></t:tracked-changes>
+            <t:p>kept<office:annotation><t:p>a comment</t:p></office:annotation> text</t:p>"#,
+        );
+        let texts: Vec<String> = paragraphs.iter().map(OdtParagraph::title).collect();
+        assert_eq!(texts, ["kept text"]);
+    }
+
+    #[test]
+    fn heading_titles_leave_out_note_citations() {
+        let paragraphs = parse(
+            r#"<t:h t:outline-level="2">The Voyage<t:note t:note-class="footnote"><t:note-citation>1</t:note-citation><t:note-body><t:p>A true story.</t:p></t:note-body></t:note></t:h>"#,
+        );
+        assert_eq!(paragraphs[0].level, Some(2));
+        assert_eq!(paragraphs[0].title(), "The Voyage");
+        let runs: Vec<&str> = paragraphs[0].runs.iter().map(|(run, _)| run.as_str()).collect();
+        assert_eq!(runs, ["The Voyage", "[1]"]);
+    }
+}
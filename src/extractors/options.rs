@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Choices that change what the extractors produce. Cached documents are
+/// keyed by them too, so changing one extracts the file again.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtractOptions {
+    /// What to do with footnotes and endnotes.
+    pub notes: NoteMode,
+    /// How much of a web page or HTML file to read.
+    pub html: HtmlMode,
+    /// The encoding of text files, such as `latin1`, instead of detecting it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteMode {
+    /// Read each note right after the paragraph that refers to it.
+    #[default]
+    Inline,
+    /// Leave notes out.
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HtmlMode {
+    /// Only the main article, without menus, sidebars and footers.
+    #[default]
+    Article,
+    /// Everything in the page's body.
+    Full,
+}
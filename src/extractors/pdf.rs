@@ -0,0 +1,316 @@
+use pdf_extract::{output_doc_page, PlainTextOutput};
+
+use super::{ExtractError, Progress};
+use crate::document::{Document, DocumentBuilder};
+
+/// How many lines at the top and bottom of a page may be running headers
+/// or footers.
+const EDGE_LINES: usize = 2;
+/// Pages on either side of a page that are compared with it.
+const NEIGHBOURS: usize = 4;
+/// Nearby pages that must repeat a line for it to be a header or footer.
+const MIN_REPEATS: usize = 2;
+/// Longer lines are body text, however often they repeat.
+const MAX_RUNNING_WORDS: usize = 12;
+
+/// Reads a PDF page by page, opening a section at every outline
+/// (bookmark) entry so the book's chapters can be navigated, and
+/// recording the page of every word. Running headers, footers and page
+/// numbers are dropped and words hyphenated at a line end are joined
+/// again. Reports progress in pages.
+pub fn extract_pdf(file_path: &str, progress: &Progress) -> Result<Document, ExtractError> {
+    let mut pdf = lopdf::Document::load(file_path).map_err(|e| match e {
+        lopdf::Error::IO(e) => ExtractError::io(file_path, e),
+        e => ExtractError::corrupt(file_path, format!("not a valid PDF: {}", e)),
+    })?;
+    if pdf.is_encrypted() {
+        // Many PDFs are "encrypted" with an empty user password.
+        let _ = pdf.decrypt("");
+    }
+
+    let outline = pdf.get_toc().map(|toc| toc.toc).unwrap_or_default();
+    let pages: Vec<u32> = pdf.get_pages().keys().copied().collect();
+
+    let mut texts = Vec::with_capacity(pages.len());
+    for (done, &page) in pages.iter().enumerate() {
+        progress.update(file_path, done, pages.len())?;
+        let mut text = String::new();
+        // Skip pages pdf-extract cannot decode instead of losing the book.
+        if output_doc_page(&pdf, &mut PlainTextOutput::new(&mut text), page).is_err() {
+            text.clear();
+        }
+        texts.push(text.lines().map(str::to_string).collect::<Vec<_>>());
+    }
+    progress.update(file_path, pages.len(), pages.len())?;
+
+    remove_running_lines(&mut texts);
+    join_hyphenated(&mut texts);
+
+    let mut builder = DocumentBuilder::new();
+    for (&page, lines) in pages.iter().zip(&texts) {
+        builder.start_page(page);
+        for entry in outline.iter().filter(|entry| entry.page == page as usize) {
+            let title = entry.title.trim();
+            let title = (!title.is_empty()).then(|| title.to_string());
+            builder.start_section(title, entry.level.min(u8::MAX as usize) as u8);
+        }
+
+        for line in lines {
+            if line.trim().is_empty() {
+                builder.end_paragraph();
+            } else {
+                builder.push_text(line);
+                builder.push_text("\n");
+            }
+        }
+        // A paragraph carries on over the page unless the page ends a sentence.
+        let last = lines.iter().rev().find(|line| !line.trim().is_empty());
+        if last.is_none_or(|line| line.trim_end().ends_with(['.', '!', '?', ':', '"', '”', '’'])) {
+            builder.end_paragraph();
+        }
+    }
+
+    let document = builder.finish();
+    if document.is_empty() {
+        return Err(ExtractError::Empty { path: file_path.to_string() });
+    }
+
+    Ok(document)
+}
+
+/// Drops lines at the top or bottom of a page that nearby pages repeat at
+/// the same edge, such as the book's title, the chapter's title or the
+/// page number. Numbers are ignored when comparing, so "Page 4" and
+/// "Page 5" count as the same line.
+fn remove_running_lines(pages: &mut [Vec<String>]) {
+    let tops: Vec<_> = pages.iter().map(|lines| edge_lines(lines.iter().enumerate())).collect();
+    let bottoms: Vec<_> = pages.iter().map(|lines| edge_lines(lines.iter().enumerate().rev())).collect();
+
+    let mut running: Vec<Vec<usize>> = vec![Vec::new(); pages.len()];
+    for edges in [&tops, &bottoms] {
+        for (page, lines) in edges.iter().enumerate() {
+            let nearby = page.saturating_sub(NEIGHBOURS)..(page + NEIGHBOURS + 1).min(pages.len());
+            for (index, key) in lines {
+                let repeats = nearby
+                    .clone()
+                    .filter(|&other| other != page && edges[other].iter().any(|(_, other_key)| other_key == key))
+                    .count();
+                if repeats >= MIN_REPEATS {
+                    running[page].push(*index);
+                }
+            }
+        }
+    }
+
+    for (lines, running) in pages.iter_mut().zip(running) {
+        let mut index = 0;
+        lines.retain(|_| {
+            index += 1;
+            !running.contains(&(index - 1))
+        });
+    }
+}
+
+/// The first few non-blank lines `lines` yields that are short enough to
+/// be headers or footers, with the key they are compared by.
+fn edge_lines<'a>(lines: impl Iterator<Item = (usize, &'a String)>) -> Vec<(usize, String)> {
+    lines
+        .filter(|(_, line)| !line.trim().is_empty())
+        .take(EDGE_LINES)
+        .filter(|(_, line)| line.split_whitespace().count() <= MAX_RUNNING_WORDS)
+        .map(|(index, line)| (index, running_key(line)))
+        .collect()
+}
+
+/// `line` in lower case with each number replaced by `#`. A line that is
+/// only a roman numeral is a page number too.
+fn running_key(line: &str) -> String {
+    let line = line.trim().to_lowercase();
+    if line.len() <= 6 && is_roman_numeral(&line) {
+        return "#".to_string();
+    }
+    let mut key = String::new();
+    for word in line.split_whitespace() {
+        if !key.is_empty() {
+            key.push(' ');
+        }
+        for c in word.chars() {
+            if !c.is_ascii_digit() {
+                key.push(c);
+            } else if !key.ends_with('#') {
+                key.push('#');
+            }
+        }
+    }
+    key
+}
+
+/// Whether `text` is a lower-case roman numeral below 400, the form of
+/// `^(c{0,3})(xc|xl|l?x{0,3})(ix|iv|v?i{0,3})$`.
+fn is_roman_numeral(text: &str) -> bool {
+    let hundreds = text.len() - text.trim_start_matches('c').len();
+    if text.is_empty() || hundreds > 3 {
+        return false;
+    }
+    let rest = strip_roman_digit(&text[hundreds..], 'x', 'l', 'c');
+    strip_roman_digit(rest, 'i', 'v', 'x').is_empty()
+}
+
+/// Strips one roman digit written with `one`, `five` and `ten`, such as
+/// "ix", "iv" or "viii", from the start of `text`.
+fn strip_roman_digit(text: &str, one: char, five: char, ten: char) -> &str {
+    for next in [ten, five] {
+        if let Some(rest) = text.strip_prefix(one).and_then(|rest| rest.strip_prefix(next)) {
+            return rest;
+        }
+    }
+    let text = text.strip_prefix(five).unwrap_or(text);
+    let ones = text.len() - text.trim_start_matches(one).len();
+    &text[ones.min(3)..]
+}
+
+/// Joins words hyphenated at the end of a line, within a paragraph or
+/// over a page break, so "infor-" and "mation" are read as "information".
+fn join_hyphenated(pages: &mut [Vec<String>]) {
+    for page in 0..pages.len() {
+        let lines = &mut pages[page];
+        let mut i = 0;
+        while i + 1 < lines.len() {
+            let (head, tail) = lines.split_at_mut(i + 1);
+            if join_broken_word(&mut head[i], &mut tail[0]) && tail[0].is_empty() {
+                lines.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+
+        let (done, rest) = pages.split_at_mut(page + 1);
+        let Some(next) = rest.first_mut() else {
+            break;
+        };
+        let last = done[page].iter_mut().rev().find(|line| !line.trim().is_empty());
+        let first = next.iter().position(|line| !line.trim().is_empty());
+        if let (Some(last), Some(first)) = (last, first) {
+            if join_broken_word(last, &mut next[first]) && next[first].is_empty() {
+                next.remove(first);
+            }
+        }
+    }
+}
+
+/// Moves the rest of a word broken at the end of `line` from the start of
+/// `next`. The hyphen is dropped when a lower-case letter follows it, or
+/// when it is a soft hyphen; "Anglo-" and "Saxon" become "Anglo-Saxon".
+fn join_broken_word(line: &mut String, next: &mut String) -> bool {
+    let trimmed = line.trim_end();
+    let soft = trimmed.ends_with('\u{ad}');
+    let Some(stem) = trimmed.strip_suffix(['-', '\u{ad}']) else {
+        return false;
+    };
+    let rest = next.trim_start();
+    if !stem.ends_with(char::is_alphabetic) || !rest.starts_with(char::is_alphabetic) {
+        return false;
+    }
+    let hyphen = if soft || rest.starts_with(char::is_lowercase) { "" } else { "-" };
+
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    *line = format!("{}{}{}", stem, hyphen, &rest[..end]);
+    *next = rest[end..].trim_start().to_string();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(pages: &[&[&str]]) -> Vec<Vec<String>> {
+        pages
+            .iter()
+            .map(|lines| lines.iter().map(|line| line.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn drops_numbered_footers() {
+        let mut texts = pages(&[
+            &["It was a dark night.", "", "Page 1"],
+            &["The rain fell.", "", "Page 2"],
+            &["Nobody came.", "", "Page 3"],
+            &["Then somebody did.", "", "Page 4"],
+        ]);
+        remove_running_lines(&mut texts);
+        assert_eq!(
+            texts,
+            pages(&[&["It was a dark night.", ""], &["The rain fell.", ""], &["Nobody came.", ""], &["Then somebody did.", ""]])
+        );
+    }
+
+    #[test]
+    fn drops_a_repeated_chapter_title_and_roman_page_numbers() {
+        let mut texts = pages(&[
+            &["The Long Voyage", "We set sail at dawn.", "xii"],
+            &["The Long Voyage", "By noon the coast was gone.", "xiii"],
+            &["The Long Voyage", "Storms came in the night.", "xiv"],
+        ]);
+        remove_running_lines(&mut texts);
+        assert_eq!(
+            texts,
+            pages(&[&["We set sail at dawn."], &["By noon the coast was gone."], &["Storms came in the night."]])
+        );
+    }
+
+    #[test]
+    fn keeps_lines_that_do_not_repeat() {
+        let original = pages(&[&["Once upon a time", "a"], &["there lived", "b"], &["a king", "c"]]);
+        let mut texts = original.clone();
+        remove_running_lines(&mut texts);
+        assert_eq!(texts, original);
+    }
+
+    #[test]
+    fn only_real_roman_numerals_are_page_numbers() {
+        for numeral in ["i", "iv", "ix", "xiv", "xl", "lxxvii", "cxc", "CCC"] {
+            assert_eq!(running_key(numeral), "#", "{}", numeral);
+        }
+        for word in ["civil", "ill", "mix", "iiii", "vv", "ic", "lil"] {
+            assert_eq!(running_key(word), word, "{}", word);
+        }
+        assert_eq!(running_key("Page 12 of 300"), "page # of #");
+    }
+
+    #[test]
+    fn joins_a_word_hyphenated_at_a_line_end() {
+        let mut texts = pages(&[&["a great deal of infor-", "mation about it"]]);
+        join_hyphenated(&mut texts);
+        assert_eq!(texts, pages(&[&["a great deal of information", "about it"]]));
+    }
+
+    #[test]
+    fn keeps_the_hyphen_before_a_capital() {
+        let mut texts = pages(&[&["the Anglo-", "Saxon Chronicle"]]);
+        join_hyphenated(&mut texts);
+        assert_eq!(texts, pages(&[&["the Anglo-Saxon", "Chronicle"]]));
+    }
+
+    #[test]
+    fn drops_a_soft_hyphen() {
+        let mut texts = pages(&[&["a per\u{ad}", "son of note"]]);
+        join_hyphenated(&mut texts);
+        assert_eq!(texts, pages(&[&["a person", "of note"]]));
+    }
+
+    #[test]
+    fn drops_a_soft_hyphen_before_a_capital_too() {
+        // A soft hyphen is never printed, so it is not kept the way "Anglo-" keeps its hyphen.
+        let mut texts = pages(&[&["old Mac\u{ad}", "Donald had"]]);
+        join_hyphenated(&mut texts);
+        assert_eq!(texts, pages(&[&["old MacDonald", "had"]]));
+    }
+
+    #[test]
+    fn joins_a_word_hyphenated_across_a_page_break() {
+        let mut texts = pages(&[&["the story con-", ""], &["", "tinued"], &["next page"]]);
+        join_hyphenated(&mut texts);
+        assert_eq!(texts, pages(&[&["the story continued", ""], &[""], &["next page"]]));
+    }
+}
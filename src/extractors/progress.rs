@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::ExtractError;
+
+/// How far an extractor has got, shared with whoever is waiting for it,
+/// and a way for them to ask it to stop.
+#[derive(Debug, Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    /// Records that `done` of `total` steps (pages, chapters) are finished,
+    /// failing with [`ExtractError::Cancelled`] once [`cancel`](Self::cancel)
+    /// has been called.
+    pub fn update(&self, path: &str, done: usize, total: usize) -> Result<(), ExtractError> {
+        self.done.store(done, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        if self.is_cancelled() {
+            return Err(ExtractError::Cancelled { path: path.to_string() });
+        }
+        Ok(())
+    }
+
+    /// Steps finished and the total, which is 0 until it is known.
+    pub fn get(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed))
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,173 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+use std::fs;
+
+use super::{ExtractError, ExtractOptions};
+
+/// How much of the start of a file is looked at to guess its encoding.
+const SNIFF_BYTES: usize = 4096;
+/// Share of control characters above which a file is taken to be binary.
+const MAX_CONTROL_SHARE: f64 = 0.05;
+
+/// Reads a text file into a string, returning it with the name of the
+/// encoding it was decoded from.
+///
+/// The encoding is `options.encoding` when given, even over a byte order
+/// mark, and an [`ExtractError::UnknownEncoding`] when it names none.
+/// Otherwise it is the one named by a byte order mark, UTF-16 if every
+/// other byte is zero, UTF-8 if the file is valid UTF-8, the charset the
+/// file declares (an HTML `<meta>` or a Project Gutenberg header), and
+/// Windows-1252 as a last resort, which decodes any byte. Files that look
+/// binary are an [`ExtractError::Encoding`].
+pub fn read_text(file_path: &str, options: &ExtractOptions) -> Result<(String, &'static str), ExtractError> {
+    let bytes = fs::read(file_path).map_err(|e| ExtractError::io(file_path, e))?;
+    let (text, encoding) = decode(&bytes, options.encoding.as_deref())?;
+    if looks_binary(&text) {
+        return Err(ExtractError::Encoding { path: file_path.to_string() });
+    }
+    Ok((text.into_owned(), encoding.name()))
+}
+
+/// Decodes `bytes` from the encoding `label` names, or from the one they
+/// seem to be in when `label` is `None`.
+fn decode<'a>(bytes: &'a [u8], label: Option<&str>) -> Result<(Cow<'a, str>, &'static Encoding), ExtractError> {
+    match label {
+        Some(label) => {
+            let encoding = Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| ExtractError::UnknownEncoding { label: label.to_string() })?;
+            Ok((encoding.decode_without_bom_handling(bytes).0, encoding))
+        }
+        None => {
+            let (text, encoding, _) = detect(bytes).decode(bytes);
+            Ok((text, encoding))
+        }
+    }
+}
+
+/// Guesses the encoding of `bytes`.
+fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    let start = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    let pairs = start.len() / 2;
+    if pairs > 0 {
+        let zeros = |offset: usize| start.iter().skip(offset).step_by(2).filter(|&&byte| byte == 0).count();
+        // ASCII text in UTF-16 has a zero in every other byte.
+        if zeros(1) * 10 >= pairs * 4 && zeros(0) * 10 < pairs {
+            return UTF_16LE;
+        }
+        if zeros(0) * 10 >= pairs * 4 && zeros(1) * 10 < pairs {
+            return UTF_16BE;
+        }
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    declared(start).unwrap_or(WINDOWS_1252)
+}
+
+/// The encoding named near the start of a file, as in
+/// `<meta charset="iso-8859-1">` or `Character set encoding: ISO-8859-1`.
+fn declared(start: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(start).to_lowercase();
+    ["charset=", "character set encoding:"].iter().find_map(|marker| {
+        let label = head[head.find(marker)? + marker.len()..]
+            .trim_start_matches([' ', '"', '\''])
+            .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+            .next()?;
+        // A page cannot be UTF-8 if it failed to decode as UTF-8.
+        Encoding::for_label(label.as_bytes()).filter(|&encoding| encoding != UTF_8)
+    })
+}
+
+/// Whether `text` has more control characters than text files do, which
+/// means it was never text at all.
+fn looks_binary(text: &str) -> bool {
+    let sample: Vec<char> = text.chars().take(SNIFF_BYTES).collect();
+    let controls = sample
+        .iter()
+        .filter(|&&c| c.is_control() && !matches!(c, '\n' | '\r' | '\t' | '\u{c}'))
+        .count();
+    !sample.is_empty() && controls as f64 > sample.len() as f64 * MAX_CONTROL_SHARE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::KOI8_R;
+
+    fn utf_16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn detects_utf_8() {
+        assert_eq!(detect("Caf\u{e9} cr\u{e8}me, na\u{ef}ve".as_bytes()), UTF_8);
+        assert_eq!(detect(b"plain ASCII"), UTF_8);
+    }
+
+    #[test]
+    fn detects_a_byte_order_mark() {
+        assert_eq!(detect(b"\xEF\xBB\xBFCaf\xC3\xA9"), UTF_8);
+        assert_eq!(detect(b"\xFF\xFEH\0i\0"), UTF_16LE);
+        assert_eq!(detect(b"\xFE\xFF\0H\0i"), UTF_16BE);
+    }
+
+    #[test]
+    fn detects_utf_16_without_a_byte_order_mark() {
+        assert_eq!(detect(&utf_16("The Time Machine, by H. G. Wells", false)), UTF_16LE);
+        assert_eq!(detect(&utf_16("The Time Machine, by H. G. Wells", true)), UTF_16BE);
+    }
+
+    #[test]
+    fn reads_the_charset_of_a_gutenberg_header() {
+        let header = b"Title: Les Mis\xE9rables\r\nCharacter set encoding: ISO-8859-1\r\n\r\nCosette";
+        assert_eq!(declared(header), Some(WINDOWS_1252));
+        assert_eq!(detect(header), WINDOWS_1252);
+        let (text, _, _) = detect(header).decode(header);
+        assert!(text.starts_with("Title: Les Mis\u{e9}rables"));
+    }
+
+    #[test]
+    fn reads_the_charset_of_an_html_page() {
+        assert_eq!(declared(b"<meta charset=\"koi8-r\">"), Some(KOI8_R));
+        assert_eq!(declared(b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=KOI8-R\">"), Some(KOI8_R));
+        assert_eq!(detect(b"<meta charset='koi8-r'><p>\xF0\xD2\xC9\xD7\xC5\xD4</p>"), KOI8_R);
+    }
+
+    #[test]
+    fn ignores_a_declared_utf_8_that_does_not_decode() {
+        assert_eq!(declared(b"<meta charset=\"utf-8\">"), None);
+        assert_eq!(detect(b"<meta charset=\"utf-8\"><p>caf\xE9</p>"), WINDOWS_1252);
+        assert_eq!(declared(b"no charset here"), None);
+    }
+
+    #[test]
+    fn an_encoding_asked_for_wins_over_a_byte_order_mark() {
+        let (text, encoding) = decode(b"\xEF\xBB\xBFcaf\xC3\xA9", Some("latin1")).unwrap();
+        assert_eq!(encoding, WINDOWS_1252);
+        assert_eq!(text, "\u{ef}\u{bb}\u{bf}caf\u{c3}\u{a9}");
+        let (text, encoding) = decode(b"\xEF\xBB\xBFcaf\xC3\xA9", None).unwrap();
+        assert_eq!((&*text, encoding), ("caf\u{e9}", UTF_8));
+    }
+
+    #[test]
+    fn an_unknown_encoding_is_an_error() {
+        let error = decode(b"text", Some("klingon")).unwrap_err();
+        assert!(matches!(error, ExtractError::UnknownEncoding { ref label } if label == "klingon"));
+    }
+
+    #[test]
+    fn tells_binary_from_text() {
+        let blob: Vec<u8> = (0..=255u8).cycle().take(2048).collect();
+        let (text, _, _) = detect(&blob).decode(&blob);
+        assert!(looks_binary(&text));
+        assert!(!looks_binary("Chapter 1\r\n\tIt was a dark night.\u{c}\n"));
+        assert!(!looks_binary(""));
+    }
+}
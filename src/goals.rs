@@ -0,0 +1,236 @@
+//! Daily reading goals and streaks, worked out from the session history.
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::reading_session::{ReadingSession, SessionTracker};
+
+/// Steps used when adjusting the goal from the preferences panel.
+const WORDS_STEP: usize = 500;
+const MINUTES_STEP: u64 = 5;
+
+/// How much to read each day, stored as `daily_goal` in the config file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DailyGoal {
+    #[default]
+    Off,
+    Words(usize),
+    Minutes(u64),
+}
+
+impl DailyGoal {
+    /// Whether `words` read in `seconds` of reading meet the goal. With no
+    /// goal set, any reading counts.
+    pub fn is_met(&self, words: usize, seconds: f64) -> bool {
+        match *self {
+            DailyGoal::Off => words > 0,
+            DailyGoal::Words(target) => words >= target,
+            DailyGoal::Minutes(target) => seconds >= target as f64 * 60.0,
+        }
+    }
+
+    /// Switches between no goal, a word goal and a time goal.
+    pub fn cycle(self) -> DailyGoal {
+        match self {
+            DailyGoal::Off => DailyGoal::Words(5000),
+            DailyGoal::Words(_) => DailyGoal::Minutes(30),
+            DailyGoal::Minutes(_) => DailyGoal::Off,
+        }
+    }
+
+    pub fn increase(self) -> DailyGoal {
+        match self {
+            DailyGoal::Off => DailyGoal::Off,
+            DailyGoal::Words(words) => DailyGoal::Words(words + WORDS_STEP),
+            DailyGoal::Minutes(minutes) => DailyGoal::Minutes(minutes + MINUTES_STEP),
+        }
+    }
+
+    pub fn decrease(self) -> DailyGoal {
+        match self {
+            DailyGoal::Off => DailyGoal::Off,
+            DailyGoal::Words(words) => DailyGoal::Words(words.saturating_sub(WORDS_STEP).max(WORDS_STEP)),
+            DailyGoal::Minutes(minutes) => {
+                DailyGoal::Minutes(minutes.saturating_sub(MINUTES_STEP).max(MINUTES_STEP))
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            DailyGoal::Off => "Off".to_string(),
+            DailyGoal::Words(words) => format!("{} words", words),
+            DailyGoal::Minutes(minutes) => format!("{} minutes", minutes),
+        }
+    }
+}
+
+/// Today's progress toward the daily goal: what earlier sessions today
+/// recorded, plus the session in progress.
+pub struct GoalProgress {
+    words_before: usize,
+    seconds_before: f64,
+}
+
+impl GoalProgress {
+    pub fn new(history: &[ReadingSession]) -> GoalProgress {
+        let day = Local::now().date_naive();
+        let (words_before, seconds_before) = day_totals(history).remove(&day).unwrap_or_default();
+        GoalProgress { words_before, seconds_before }
+    }
+
+    fn totals(&self, session: &SessionTracker) -> (usize, f64) {
+        // Sessions running past midnight count toward the day they started.
+        (
+            self.words_before + session.words_read,
+            self.seconds_before + session.reading_time,
+        )
+    }
+
+    pub fn is_met(&self, goal: &DailyGoal, session: &SessionTracker) -> bool {
+        let (words, seconds) = self.totals(session);
+        *goal != DailyGoal::Off && goal.is_met(words, seconds)
+    }
+
+    /// A one-line summary such as "1200 / 5000 words (24%)", or `None` when
+    /// no goal is set.
+    pub fn describe(&self, goal: &DailyGoal, session: &SessionTracker) -> Option<String> {
+        let (words, seconds) = self.totals(session);
+        let (done, target, unit) = match *goal {
+            DailyGoal::Off => return None,
+            DailyGoal::Words(target) => (words as f64, target as f64, "words"),
+            DailyGoal::Minutes(target) => (seconds / 60.0, target as f64, "minutes"),
+        };
+        let percent = if target > 0.0 { (done / target * 100.0).min(100.0) } else { 100.0 };
+        Some(format!("{:.0} / {:.0} {} ({:.0}%)", done, target, unit, percent))
+    }
+}
+
+/// Words and seconds read on each day, by local date of the session start.
+fn day_totals(history: &[ReadingSession]) -> HashMap<NaiveDate, (usize, f64)> {
+    let mut totals: HashMap<NaiveDate, (usize, f64)> = HashMap::new();
+    for session in history {
+        let day = session.date.with_timezone(&Local).date_naive();
+        let entry = totals.entry(day).or_default();
+        entry.0 += session.words_read;
+        entry.1 += session.reading_time;
+    }
+    totals
+}
+
+/// Counts consecutive days meeting `goal`, ending today, or yesterday when
+/// today's goal is not met yet, so the streak does not look broken first
+/// thing in the morning.
+pub fn streak(history: &[ReadingSession], goal: &DailyGoal) -> usize {
+    let totals = day_totals(history);
+    let met_on = |day: &NaiveDate| {
+        totals
+            .get(day)
+            .is_some_and(|(words, seconds)| goal.is_met(*words, *seconds))
+    };
+
+    let today = Local::now().date_naive();
+    let mut day = if met_on(&today) {
+        today
+    } else {
+        match today.pred_opt() {
+            Some(yesterday) => yesterday,
+            None => return 0,
+        }
+    };
+    let mut days = 0;
+    while met_on(&day) {
+        days += 1;
+        match day.pred_opt() {
+            Some(previous) => day = previous,
+            None => break,
+        }
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Days, Utc};
+
+    /// A session `days_ago` days back at noon, local time.
+    fn session(days_ago: u64, words_read: usize, minutes: f64) -> ReadingSession {
+        let day = Local::now().date_naive() - Days::new(days_ago);
+        let date = day.and_hms_opt(12, 0, 0).unwrap().and_local_timezone(Local).unwrap().with_timezone(&Utc);
+        ReadingSession {
+            date,
+            end_date: date,
+            book: "book".to_string(),
+            file_path: "/books/book.txt".to_string(),
+            words_read,
+            reading_time: minutes * 60.0,
+            avg_speed: 0,
+            start_position: 0,
+            end_position: words_read,
+            pauses: 0,
+        }
+    }
+
+    fn tracker(words_read: usize, minutes: f64) -> SessionTracker {
+        let mut session = SessionTracker::start("book", "/books/book.txt", 0);
+        session.words_read = words_read;
+        session.reading_time = minutes * 60.0;
+        session
+    }
+
+    #[test]
+    fn a_missed_day_breaks_the_streak() {
+        let history = [session(0, 1000, 5.0), session(1, 1000, 5.0), session(3, 1000, 5.0), session(4, 1000, 5.0)];
+        assert_eq!(streak(&history, &DailyGoal::Words(1000)), 2);
+    }
+
+    #[test]
+    fn the_streak_counts_up_to_yesterday_until_today_meets_the_goal() {
+        let history = [session(0, 200, 1.0), session(1, 1000, 5.0), session(2, 1000, 5.0)];
+        assert_eq!(streak(&history, &DailyGoal::Words(1000)), 2);
+        let history = [session(0, 1000, 5.0), session(1, 1000, 5.0), session(2, 1000, 5.0)];
+        assert_eq!(streak(&history, &DailyGoal::Words(1000)), 3);
+        assert_eq!(streak(&[session(2, 1000, 5.0)], &DailyGoal::Words(1000)), 0);
+    }
+
+    #[test]
+    fn without_a_goal_any_reading_day_counts() {
+        let history = [session(0, 1, 0.1), session(1, 5, 0.1), session(2, 0, 3.0), session(3, 5, 0.1)];
+        assert_eq!(streak(&history, &DailyGoal::Off), 2);
+    }
+
+    #[test]
+    fn minute_goals_count_time_and_word_goals_count_words() {
+        // Two days of few words read slowly, after plenty skimmed quickly.
+        let history = [session(0, 10, 31.0), session(1, 10, 31.0), session(2, 5000, 10.0)];
+        assert_eq!(streak(&history, &DailyGoal::Minutes(30)), 2);
+        assert_eq!(streak(&history, &DailyGoal::Words(1000)), 0);
+        assert_eq!(streak(&[session(1, 5000, 10.0)], &DailyGoal::Words(1000)), 1);
+    }
+
+    #[test]
+    fn progress_adds_the_session_in_progress_to_earlier_sessions_today() {
+        let progress = GoalProgress::new(&[session(0, 400, 10.0), session(1, 5000, 60.0)]);
+
+        assert!(!progress.is_met(&DailyGoal::Words(1000), &tracker(500, 5.0)));
+        assert!(progress.is_met(&DailyGoal::Words(1000), &tracker(600, 5.0)));
+        assert_eq!(
+            progress.describe(&DailyGoal::Words(1000), &tracker(100, 5.0)).as_deref(),
+            Some("500 / 1000 words (50%)")
+        );
+        assert_eq!(
+            progress.describe(&DailyGoal::Minutes(10), &tracker(100, 5.0)).as_deref(),
+            Some("15 / 10 minutes (100%)")
+        );
+    }
+
+    #[test]
+    fn progress_toward_no_goal_is_never_met() {
+        let progress = GoalProgress::new(&[session(0, 400, 10.0)]);
+        assert!(!progress.is_met(&DailyGoal::Off, &tracker(1000, 60.0)));
+        assert_eq!(progress.describe(&DailyGoal::Off, &tracker(1000, 60.0)), None);
+    }
+}
@@ -1,9 +1,18 @@
 #![allow(unused_mut)]
-use crate::utilities::get_adaptive_chunk_size;
+use chrono::Local;
+use crate::cache::Cache;
+use crate::goals::{self, DailyGoal, GoalProgress};
+use crate::loader::{Loaded, Loading, Source};
+use crate::reading_session::SessionTracker;
+use crate::stats::{format_duration, ReadingStats};
+use crate::settings::Settings;
+use rsvp::extractors::{ExtractError, HtmlMode};
+use rsvp::orp::{self, OrpConfig};
+use rsvp::reader::{DisplayMode, Reader};
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Alignment},
-    widgets::{Wrap, Block, Borders, Paragraph, Gauge},
+    layout::{Constraint, Direction, Layout, Alignment, Rect},
+    widgets::{BarChart, Clear, Wrap, Block, Borders, Paragraph, Gauge},
     text::{Span, Line, Text},
     Terminal,
 };
@@ -11,50 +20,187 @@ use ratatui::style::{Color, Style, Modifier};
 // use std::io::{ Write};
 use crossterm::{
     ExecutableCommand,
-    event::{self, Event, KeyCode, KeyEvent},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     terminal::{self, LeaveAlternateScreen},
 };
 use std::io::stdout;
 use std::time::{Duration, Instant};
-use crate::utilities::save_settings;
 use ratatui::{Frame}; // , backend::Backend};
 //use std::fs::OpenOptions;
 use crate::utilities;
-use std::collections::HashMap;
 //use crate::json;
-use serde_json::json;use serde_json::Value;
 use tts::{Tts};
 
 
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum DisplayMode {
-    WordChunk(usize),
-    Sentence,
+const BGRND: Color = Color::Rgb(10, 34, 171); // Background color
+const TXT: Color = Color::Rgb(63, 252, 123); // Text color
+const SCRTEXT: Color = Color::Rgb(230, 230, 250); // Screen text color
+
+/// Lays out `text` so its pivot letter lands on the middle column of a
+/// `width`-wide area, with optional fixation marks above and below it.
+fn orp_text(text: &str, width: usize, fixation_marks: bool, colour: Color) -> Text<'static> {
+    const PIVOT: Color = Color::Rgb(255, 90, 90);
+
+    let orp::OrpSplit { before, pivot, after } = orp::split(text);
+    let column = width / 2;
+    // Letters that would fall off the left edge are dropped.
+    let before_len = before.chars().count();
+    let before: String = before.chars().skip(before_len.saturating_sub(column)).collect();
+    let padding = " ".repeat(column - before.chars().count());
+
+    let word = Line::from(vec![
+        Span::raw(padding),
+        Span::styled(before, Style::default().fg(colour)),
+        Span::styled(pivot.to_string(), Style::default().fg(PIVOT).add_modifier(Modifier::BOLD)),
+        Span::styled(after.to_string(), Style::default().fg(colour)),
+    ]);
+    if !fixation_marks {
+        return Text::from(word);
+    }
+
+    let mark = |tick: char| {
+        let rule_after = width.saturating_sub(column + 1);
+        Line::from(Span::styled(
+            format!("{}{}{}", "─".repeat(column), tick, "─".repeat(rule_after)),
+            Style::default().fg(colour),
+        ))
+    };
+    Text::from(vec![mark('┬'), word, mark('┴')])
+}
+
+/// Full-screen reading statistics, built from the session history.
+fn draw_dashboard(f: &mut Frame, stats: &ReadingStats) {
+    let size = f.area();
+    f.render_widget(Block::default().style(Style::default().bg(BGRND)), size);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),      // Summary
+            Constraint::Percentage(45), // Words per day and week
+            Constraint::Min(8),         // WPM and books
+        ])
+        .split(size);
+
+    let today = stats.daily_words.last().map(|(_, words)| *words).unwrap_or(0);
+    let this_week = stats.weekly_words.last().map(|(_, words)| *words).unwrap_or(0);
+    let summary = Paragraph::new(format!(
+        "Streak: {} day{} | Today: {} words | This week: {} words | All time: {} words    [D]/[Esc] close",
+        stats.streak,
+        if stats.streak == 1 { "" } else { "s" },
+        today,
+        this_week,
+        stats.total_words
+    ))
+    .block(Block::default().borders(Borders::ALL).title("Reading Dashboard"))
+    .style(Style::default().fg(SCRTEXT).bg(BGRND));
+    f.render_widget(summary, rows[0]);
+
+    let charts = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(rows[1]);
+    let daily: Vec<(String, u64)> = stats
+        .daily_words
+        .iter()
+        .map(|(day, words)| (day.format("%d").to_string(), *words as u64))
+        .collect();
+    let weekly: Vec<(String, u64)> = stats
+        .weekly_words
+        .iter()
+        .map(|(week, words)| (week.format("%d/%m").to_string(), *words as u64))
+        .collect();
+    f.render_widget(bar_chart("Words per Day", &daily, 4), charts[0]);
+    f.render_widget(bar_chart("Words per Week", &weekly, 5), charts[1]);
+
+    let lower = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[2]);
+    let wpm: Vec<(String, u64)> = stats
+        .daily_wpm
+        .iter()
+        .map(|(day, wpm)| (day.format("%d").to_string(), *wpm))
+        .collect();
+    f.render_widget(bar_chart("Effective WPM per Day", &wpm, 4), lower[0]);
+
+    let mut book_lines = vec![Line::from(Span::styled("Time spent", Style::default().fg(TXT)))];
+    book_lines.extend(
+        stats
+            .time_per_book
+            .iter()
+            .map(|(book, seconds)| Line::from(format!("  {}: {}", book, format_duration(*seconds)))),
+    );
+    book_lines.push(Line::from(""));
+    book_lines.push(Line::from(Span::styled("Time to finish", Style::default().fg(TXT))));
+    book_lines.extend(stats.time_to_finish.iter().map(|(book, seconds)| {
+        let estimate = match seconds {
+            Some(seconds) if *seconds > 0.0 => format_duration(*seconds),
+            Some(_) => "finished".to_string(),
+            None => "unknown".to_string(),
+        };
+        Line::from(format!("  {}: {}", book, estimate))
+    }));
+    let books = Paragraph::new(book_lines)
+        .block(Block::default().borders(Borders::ALL).title("Books"))
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(SCRTEXT).bg(BGRND));
+    f.render_widget(books, lower[1]);
 }
 
-fn draw_main_ui(
-    f: &mut Frame,
-    current_word_index: usize,
-    display_mode: DisplayMode,
-    words: &[String],
-    total_words: usize,
-    speed: u64,
+fn bar_chart<'a>(title: &'a str, data: &'a [(String, u64)], bar_width: u16) -> BarChart<'a> {
+    let data: Vec<(&str, u64)> = data.iter().map(|(label, value)| (label.as_str(), *value)).collect();
+    BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .data(&data)
+        .bar_width(bar_width)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(TXT))
+        .value_style(Style::default().fg(Color::Black).bg(TXT))
+        .label_style(Style::default().fg(SCRTEXT))
+        .style(Style::default().bg(BGRND))
+}
+
+/// What the main screen shows besides the book itself: the open panels,
+/// this run's reading figures and anything in progress.
+struct ViewState {
     words_read: usize,
     reading_time: f64,
     preferences_mode: bool,
     bookmark_mode: bool,
-    pause_mode: bool,
-    bookmarks_list: &[(usize, String)],
     selected_bookmark: usize,
-    file_path: &str, // ✅ Add missing file_path
-    smart_mode: bool,
+    toc_mode: bool,
+    selected_chapter: usize,
+    file_path: String,
     tts_enabled: bool,
-) {
+    orp_config: OrpConfig,
+    /// The ORP settings being chosen in the preferences dialog, applied
+    /// to `orp_config` only when saved.
+    pending_orp: OrpConfig,
+    dashboard: Option<ReadingStats>,
+    daily_goal: DailyGoal,
+    goal_progress: GoalProgress,
+    goal_notice_until: Option<Instant>,
+    session: SessionTracker,
+    error_message: Option<String>,
+    loading: Option<Loading>,
+}
+
+fn draw_main_ui(f: &mut Frame, reader: &Reader, view: &ViewState) {
     // panic!("DEBUG: Passed current_word_index = {}", current_word_index);
-    const BGRND: Color = Color::Rgb(10, 34, 171); // Background color
-    const TXT: Color = Color::Rgb(63, 252, 123); // Text color
-    const SCRTEXT: Color = Color::Rgb(230, 230, 250); // Screen text color
+    if let Some(stats) = &view.dashboard {
+        draw_dashboard(f, stats);
+        return;
+    }
+
+    let document = reader.document();
+    let current_word_index = reader.position();
+    let display_mode = reader.mode();
+    let frame = reader.current_frame();
+    let orp_config = view.orp_config;
+    let goal_status = view.goal_progress.describe(&view.daily_goal, &view.session);
+    let goal_notice = view.goal_notice_until.is_some_and(|until| Instant::now() < until);
 
     let size = f.area();
 
@@ -62,16 +208,23 @@ fn draw_main_ui(
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Percentage(5),  // Quick Keys
-            Constraint::Percentage(if preferences_mode { 31 } else { 31 }), // Top Spacer (shrinks when preferences are active)
-            Constraint::Percentage(8),  // Text Block
-            Constraint::Percentage(if bookmark_mode {28} else {28} ), // Bottom Spacer
+            Constraint::Percentage(if display_mode == DisplayMode::Sentence { 19 } else { 31 }), // Top Spacer (shrinks to make room for sentences)
+            // Text Block (sentences wrap over several lines; fixation marks need a line above and below the word)
+            if display_mode == DisplayMode::Sentence {
+                Constraint::Percentage(20)
+            } else if orp_config.enabled && orp_config.fixation_marks {
+                Constraint::Length(5)
+            } else {
+                Constraint::Percentage(8)
+            },
+            Constraint::Percentage(if view.bookmark_mode {28} else {28} ), // Bottom Spacer
             Constraint::Percentage(26), // Stats & Progress
         ])
         .split(size);
 
     f.render_widget(Block::default().style(Style::default().bg(BGRND)), size);
 
-    let quick_keys_text = "[Q]uit | [Space] pause/resume | [L]oad File | [W]eb | [P]references | [B]ookmark | [S]entence Mode | [↑] +10 | [↓] -10 | [PgUp] +100 | [PgDn] -100 | [1-9] chunk size ";
+    let quick_keys_text = "[Q]uit | [Space] pause/resume | [L]oad File | [W]eb | [P]references | [B]ookmark | [C]ontents | [ / ] prev/next chapter | [G]o to page | [R] article/full page | [D]ashboard | [S]entence Mode | [↑] +10 | [↓] -10 | [PgUp] +100 | [PgDn] -100 | [1-9] chunk size ";
     let quick_keys = Paragraph::new(quick_keys_text)
         .block(Block::default().borders(Borders::ALL).title("Menu Keys"))
         .style(Style::default().fg(SCRTEXT).bg(BGRND));
@@ -79,11 +232,14 @@ fn draw_main_ui(
 
   
     // **PREFERENCES UI (IN TOP SPACER)**
-    if preferences_mode {
+    if view.preferences_mode {
         let preferences_text = format!(
-            "Preferences:\nSpeed: {} WPM  [↑] +10 | [↓] -10\nChunk Size: {} [←] -1 | [→] +1\n[Enter] Save | [Esc] Cancel",
-            speed,
-            if let DisplayMode::WordChunk(size) = display_mode { size.to_string() } else { "Sentence".to_string() }
+            "Preferences:\nSpeed: {} WPM (words, not chunks)  [↑] +10 | [↓] -10\nChunk Size: {} [←] -1 | [→] +1\nORP Alignment: {} [O] toggle\nFixation Marks: {} [F] toggle\nDaily Goal: {} [G] change | [+]/[-] adjust\n[Enter] Save | [Esc] Cancel",
+            reader.wpm(),
+            if let DisplayMode::WordChunk(size) = display_mode { size.to_string() } else { "Sentence".to_string() },
+            if view.pending_orp.enabled { "On" } else { "Off" },
+            if view.pending_orp.fixation_marks { "On" } else { "Off" },
+            view.daily_goal.describe()
         );
 
         let preferences_block = Paragraph::new(preferences_text)
@@ -94,20 +250,57 @@ fn draw_main_ui(
     } else {
         let top_spacer = Block::default().style(Style::default().bg(BGRND));
         f.render_widget(top_spacer, chunks[1]);
+
+        if goal_notice {
+            // Small banner at the bottom of the top spacer, just above the text
+            let width = 36.min(chunks[1].width);
+            let height = 3.min(chunks[1].height);
+            let area = Rect::new(
+                chunks[1].x + (chunks[1].width - width) / 2,
+                chunks[1].y + chunks[1].height - height,
+                width,
+                height,
+            );
+            let notice = Paragraph::new("Daily goal reached, well done!")
+                .alignment(Alignment::Center)
+                .block(Block::default().borders(Borders::ALL))
+                .style(Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD));
+            f.render_widget(notice, area);
+        }
+
+        if let Some(loading) = &view.loading {
+            // Progress box at the top of the top spacer; the text keeps running below it
+            let area = Rect::new(chunks[1].x, chunks[1].y, chunks[1].width, 3.min(chunks[1].height));
+            let (done, total) = loading.progress();
+            let title = format!("{} Loading {} | [Esc] cancel", loading.spinner(), loading.source.name());
+            let block = Block::default().borders(Borders::ALL).title(title);
+            if total > 0 {
+                let gauge = Gauge::default()
+                    .block(block)
+                    .gauge_style(Style::default().fg(Color::Yellow).bg(Color::Black))
+                    .label(format!("{} / {}", done, total))
+                    .ratio((done as f64 / total as f64).min(1.0));
+                f.render_widget(gauge, area);
+            } else {
+                let waiting = Paragraph::new("Please wait...")
+                    .block(block)
+                    .style(Style::default().fg(Color::Yellow).bg(Color::Black));
+                f.render_widget(waiting, area);
+            }
+        }
     }
 
     // **BOOKMARK/PAUSE UI (IN BOTTOM SPACER)**    
-    if pause_mode {
-        let chunk_size = if let DisplayMode::WordChunk(size) = display_mode { size } else { 1 };
+    if reader.is_paused() {
         // Define window for context (20 words before and 20 words after the chunk)
         let before_start = current_word_index.saturating_sub(20);
-        let chunk_end = (current_word_index + chunk_size).min(words.len());
-        let after_end = (chunk_end + 20).min(words.len());
+        let chunk_end = frame.range.end;
+        let after_end = (chunk_end + 20).min(document.len());
 
         // Get surrounding context
-        let before_text = words[before_start..current_word_index].join(" ");
-        let current_chunk = words[current_word_index..chunk_end].join(" ").to_uppercase(); // ✅ Show the full chunk
-        let after_text = words[chunk_end..after_end].join(" ");
+        let before_text = document.join(before_start..current_word_index);
+        let current_chunk = document.join(current_word_index..chunk_end).to_uppercase(); // ✅ Show the full chunk
+        let after_text = document.join(chunk_end..after_end);
         let margin = "          "; // Define a left and right margin (spaces)
 
         // Define styling
@@ -132,30 +325,81 @@ fn draw_main_ui(
             .style(Style::default().bg(Color::Black));
 
         f.render_widget(context_block, chunks[3]);
-    } else if bookmark_mode {
-        // Clear panel before displaying bookmarks
-        let mut bookmark_items = vec!["=> Create Bookmark".to_string()];
-        // for (i, (index, preview)) in bookmarks_list.iter().enumerate() {
-        //     let selected = if i + 1 == selected_bookmark { "=>" } else { "  " };
-        //     bookmark_items.push(format!("{} Word #{} ({})", selected, index, preview));
-        // }
+    } else if view.bookmark_mode {
+        // Row 0 creates a bookmark; row i selects bookmark i - 1.
+        let create = if view.selected_bookmark == 0 { "=>" } else { "  " };
+        let mut bookmark_items = vec![format!("{} Create Bookmark", create)];
         let max_display = 10; // ✅ Limit display to 10 bookmarks at a time
-        let start_index = selected_bookmark.saturating_sub(max_display / 2);
-        let end_index = (start_index + max_display).min(bookmarks_list.len());
-
-        for (i, (index, preview)) in bookmarks_list[start_index..end_index].iter().enumerate() {
-            let selected = if i + start_index == selected_bookmark { "=>" } else { "  " };
-            bookmark_items.push(format!("{} Word #{} ({})", selected, index, preview));
+        let start_index = view.selected_bookmark.saturating_sub(max_display / 2);
+        let document = reader.document();
+
+        for (i, bookmark) in reader.bookmarks().iter().enumerate().skip(start_index).take(max_display) {
+            let selected = if i + 1 == view.selected_bookmark { "=>" } else { "  " };
+            let mut details = vec![match document.page_at(bookmark.position) {
+                Some(page) => format!("Page {}, word #{}", page, bookmark.position),
+                None => format!("Word #{}", bookmark.position),
+            }];
+            if let Some(title) = document
+                .section_at(bookmark.position)
+                .and_then(|section| document.sections[section].title.as_deref())
+            {
+                details.insert(0, title.to_string());
+            }
+            if let Some(created) = bookmark.created {
+                details.push(created.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string());
+            }
+            let lost = if bookmark.lost { " [text not found]" } else { "" };
+            bookmark_items.push(format!("{} {} ({}){}", selected, bookmark.label(), details.join(", "), lost));
+            if !bookmark.note.is_empty() {
+                bookmark_items.push(format!("     {}", bookmark.note));
+            }
         }
 
 
         let bookmark_text = bookmark_items.join("\n");
 
         let bookmark_block = Paragraph::new(bookmark_text)
-            .block(Block::default().borders(Borders::ALL).title("Bookmarks"))
+            .block(Block::default().borders(Borders::ALL).title(
+                "Bookmarks: [Enter] Go  [R] Rename  [N] Note  [X] Delete  [Shift+Up/Down] Move",
+            ))
             .style(Style::default().fg(Color::Yellow).bg(Color::Black));
 
         f.render_widget(bookmark_block, chunks[3]);
+    } else if view.toc_mode {
+        // Table of contents, scrolled to keep the selected chapter visible
+        let max_display = chunks[3].height.saturating_sub(2).max(1) as usize;
+        let start_index = view.selected_chapter.saturating_sub(max_display / 2);
+        let end_index = (start_index + max_display).min(document.sections.len());
+        let total = document.len().max(1);
+
+        let toc_items: Vec<String> = document.sections[start_index..end_index]
+            .iter()
+            .enumerate()
+            .map(|(i, section)| {
+                let selected = if i + start_index == view.selected_chapter { "=>" } else { "  " };
+                let indent = "  ".repeat(section.level.saturating_sub(1) as usize);
+                format!(
+                    "{} {}{} (word {}, {:.0}%)",
+                    selected,
+                    indent,
+                    section.title.as_deref().unwrap_or("Untitled"),
+                    section.tokens.start,
+                    section.tokens.start as f64 * 100.0 / total as f64
+                )
+            })
+            .collect();
+
+        let toc_text = if toc_items.is_empty() {
+            "This document has no chapters".to_string()
+        } else {
+            toc_items.join("\n")
+        };
+
+        let toc_block = Paragraph::new(toc_text)
+            .block(Block::default().borders(Borders::ALL).title("Contents"))
+            .style(Style::default().fg(Color::Yellow).bg(Color::Black));
+
+        f.render_widget(toc_block, chunks[3]);
     } else {
         // Default blank panel
         let bottom_spacer = Block::default().style(Style::default().bg(BGRND));
@@ -163,30 +407,28 @@ fn draw_main_ui(
     }
 
     // **Text Block**
-    let word_display = if current_word_index < words.len() {
-        match display_mode {
-            DisplayMode::WordChunk(chunk_size) => {
-                let display_chunk_size = if smart_mode {
-                    get_adaptive_chunk_size(words, current_word_index, chunk_size)
-                } else {
-                    chunk_size
-                };
-                words[current_word_index..current_word_index + display_chunk_size.min(words.len() - current_word_index)].join(" ")
-            }
-            DisplayMode::Sentence => {
-                // For now, just display the current word.
-                // We will implement sentence splitting later.
-                words[current_word_index].clone()
-            }
-        }
+    let word_display = if !frame.range.is_empty() {
+        frame.text()
     } else {
         "End of text".to_string()
     };
-    let styled_text = Text::from(Span::styled(word_display, Style::default().fg(TXT)));
-    let text_content = Paragraph::new(styled_text)
+    // ORP alignment only applies to word chunks; sentences stay centred.
+    let orp_mode = orp_config.enabled
+        && !frame.range.is_empty()
+        && matches!(display_mode, DisplayMode::WordChunk(_));
+    let styled_text = if orp_mode {
+        let width = chunks[2].width.saturating_sub(2) as usize;
+        orp_text(&word_display, width, orp_config.fixation_marks, TXT)
+    } else {
+        Text::from(Span::styled(word_display, Style::default().fg(TXT)))
+    };
+    let mut text_content = Paragraph::new(styled_text)
         .block(Block::default().borders(Borders::ALL).title("Text"))
-        .alignment(Alignment::Center)
+        .alignment(if orp_mode { Alignment::Left } else { Alignment::Center })
         .style(Style::default().bg(BGRND).fg(TXT));
+    if !orp_mode {
+        text_content = text_content.wrap(Wrap { trim: true });
+    }
     f.render_widget(text_content, chunks[2]);
 
     // **Bottom Spacer**
@@ -206,11 +448,27 @@ fn draw_main_ui(
         .split(stats_chunks[0]);
 
     // Use the `file_path` that is passed to the function
-    let file_path = file_path.to_string();
+    let file_path = view.file_path.to_string();
+
+    let chapter = document
+        .section_at(current_word_index)
+        .and_then(|i| document.sections[i].title.as_deref())
+        .unwrap_or("-");
+    let page = match (document.page_at(current_word_index), document.pages.last()) {
+        (Some(page), Some(last)) => format!("\nPage: {} of {}", page, last.number),
+        _ => String::new(),
+    };
+
+    let encoding = document
+        .encoding
+        .as_deref()
+        .map(|encoding| format!("\nEncoding: {}", encoding))
+        .unwrap_or_default();
 
     let left_stats_text = format!(
-        "\nFile: {}\nWords Read This Session: {}\nTotal Words: {} of {}\nReading Time: {:.2} seconds\nCurrent Position: {}",
-        file_path.to_string(), words_read, words_read, total_words, reading_time, current_word_index
+        "\nFile: {}{}\nChapter: {}{}\nWords Read This Session: {}\nTotal Words: {} of {}\nReading Time: {:.2} seconds\nCurrent Position: {}{}",
+        file_path.to_string(), encoding, chapter, page, view.words_read, view.words_read, document.len(), view.reading_time, current_word_index,
+        goal_status.map(|status| format!("\nDaily Goal: {}", status)).unwrap_or_default()
     );
     
     let left_stats = Paragraph::new(left_stats_text)
@@ -220,14 +478,15 @@ fn draw_main_ui(
 
     // **Right Stats**
     let right_stats_text = format!(
-        "\nSpeed: {} WPM\nDisplay Mode: {}\nSmart Mode: {}\nTTS: {}",
-        speed,
+        "\nSpeed: {} WPM\nDisplay Mode: {}\nSmart Mode: {}\nTTS: {}\nORP: {}",
+        reader.wpm(),
         match display_mode {
             DisplayMode::WordChunk(size) => format!("Chunk ({})", size),
             DisplayMode::Sentence => "Sentence".to_string(),
         },
-        if smart_mode { "On" } else { "Off" },
-        if tts_enabled { "On" } else { "Off" }
+        if reader.smart_chunking() { "On" } else { "Off" },
+        if view.tts_enabled { "On" } else { "Off" },
+        if orp_config.enabled { "On" } else { "Off" }
     );
     let right_stats = Paragraph::new(right_stats_text)
         .block(Block::default().borders(Borders::ALL).title("Settings"))
@@ -235,8 +494,8 @@ fn draw_main_ui(
     f.render_widget(right_stats, stats_split[1]);
 
     // **Progress Bar**
-    let progress_ratio = if total_words > 0 {
-        current_word_index as f64 / total_words as f64
+    let progress_ratio = if !document.is_empty() {
+        current_word_index as f64 / document.len() as f64
     } else {
         0.0
     };
@@ -247,430 +506,654 @@ fn draw_main_ui(
         .gauge_style(Style::default().fg(Color::Green).bg(BGRND))
         .ratio(progress_ratio);
     f.render_widget(progress_bar, stats_chunks[1]);
+
+    // **Error Popup** (over everything else, until dismissed)
+    if let Some(message) = &view.error_message {
+        let width = (size.width * 3 / 5).max(30).min(size.width);
+        let height = 7.min(size.height);
+        let area = Rect::new(
+            size.x + (size.width - width) / 2,
+            size.y + (size.height - height) / 2,
+            width,
+            height,
+        );
+        let popup = Paragraph::new(format!("{}\n\n[Enter]/[Esc] dismiss", message))
+            .block(Block::default().borders(Borders::ALL).title("Error"))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .style(Style::default().fg(Color::White).bg(Color::Red));
+        f.render_widget(Clear, area);
+        f.render_widget(popup, area);
+    }
 }
 
 
+/// Runs the reader until the user quits, then saves where they stopped in
+/// whichever book is open. `book` is its key in `settings.books`.
 pub fn run_ui(
+    mut reader: Reader,
+    settings: &mut Settings,
+    mut book: String,
+    file_path: String,
+) {
 
 
-    mut speed: u64,
-
-
-    mut display_mode: DisplayMode,
-
 
-    mut total_words: usize,
 
 
-    mut words: Vec<String>,
 
 
-    book_data: &mut HashMap<String, Value>,
 
 
-    global_speed: u64,
 
 
-    global_chunk_size: usize,
+    let mut consume_next_event = false;
 
 
-    mut file_path: String,
 
 
-    mut current_word_index: usize,
 
 
-) -> usize {
 
 
-    let mut chunk_size = if let DisplayMode::WordChunk(size) = display_mode { size } else { 1 };
 
 
 
+    let mut stdout = stdout();
 
 
-    let mut preferences_mode = false;
+    terminal::enable_raw_mode().unwrap();
 
 
-    let mut bookmark_mode = false;
+    stdout.execute(terminal::EnterAlternateScreen).unwrap();
 
 
-    let mut consume_next_event = false;
+    let backend = CrosstermBackend::new(stdout);
 
 
-    let mut word_delay = Duration::from_millis(60000 / speed);
+    let mut terminal = Terminal::new(backend).unwrap();
 
 
-    let mut last_update = Instant::now();
 
 
 
 
 
-    let mut stdout = stdout();
 
 
-    terminal::enable_raw_mode().unwrap();
 
 
-    stdout.execute(terminal::EnterAlternateScreen).unwrap();
 
 
-    let backend = CrosstermBackend::new(stdout);
 
 
-    let mut terminal = Terminal::new(backend).unwrap();
 
 
 
 
 
-    let mut words_read = 0;
 
 
-    let mut reading_time = 0.0;
 
 
-    let mut bookmarks_list: Vec<(usize, String)> = vec![];
 
 
-    let mut file_path = file_path.clone(); // Ensure we're using the correct file
 
+    let mut tts = Tts::new(tts::Backends::SpeechDispatcher).unwrap();
+    let session = SessionTracker::start(&book, &file_path, reader.position());
+    let daily_goal = settings.global.daily_goal;
+    let goal_progress = GoalProgress::new(&settings.history());
+    // Only announce the goal when it is reached during this run.
+    let mut goal_announced = goal_progress.is_met(&daily_goal, &session);
+    let mut view = ViewState {
+        words_read: 0,
+        reading_time: 0.0,
+        preferences_mode: false,
+        bookmark_mode: false,
+        selected_bookmark: 0,
+        toc_mode: false,
+        selected_chapter: 0,
+        file_path,
+        tts_enabled: false,
+        orp_config: settings.global.orp,
+        pending_orp: settings.global.orp,
+        dashboard: None,
+        daily_goal,
+        goal_progress,
+        goal_notice_until: None,
+        session,
+        error_message: None,
+        loading: None,
+    };
+    let cache = Cache::new(settings.cache_dir(), settings.global.max_cache_mb);
 
 
 
 
-    let mut selected_bookmark = 0;
 
+    terminal.draw(|f| draw_main_ui(f, &reader, &view)).unwrap();
 
-    let mut pause_mode = false;
 
 
-    let mut smart_mode = false;
 
 
-    let mut tts_enabled = false;
+    loop {
 
 
-    let mut tts = Tts::new(tts::Backends::SpeechDispatcher).unwrap();
+        if event::poll(Duration::from_millis(10)).unwrap() {
 
 
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event::read().unwrap() {
 
 
+                if consume_next_event {
 
-    terminal
 
+                    consume_next_event = false;
 
-        .draw(|f| {
 
+                    continue;
 
-            draw_main_ui(
 
+                }
 
-                f,
 
 
-                current_word_index,
 
 
-                display_mode,
+                if view.error_message.is_some() {
+                    if let KeyCode::Esc | KeyCode::Enter = code {
+                        view.error_message = None;
+                    }
+                } else if view.dashboard.is_some() {
+                    if let KeyCode::Esc | KeyCode::Char('d') = code {
+                        view.dashboard = None;
+                    }
+                } else if view.bookmark_mode {
+                    let shift = modifiers.contains(KeyModifiers::SHIFT);
+                    match code {
+                        KeyCode::Up if shift && view.selected_bookmark > 1 => {
+                            reader.bookmarks_mut().swap(view.selected_bookmark - 1, view.selected_bookmark - 2);
+                            view.selected_bookmark -= 1;
+                            settings.books.entry(book.clone()).or_default().remember(&reader);
+                            if let Err(err) = settings.save() {
+                                view.error_message = Some(err.to_string());
+                            }
+                        }
+                        KeyCode::Down if shift && view.selected_bookmark > 0 && view.selected_bookmark < reader.bookmarks().len() => {
+                            reader.bookmarks_mut().swap(view.selected_bookmark - 1, view.selected_bookmark);
+                            view.selected_bookmark += 1;
+                            settings.books.entry(book.clone()).or_default().remember(&reader);
+                            if let Err(err) = settings.save() {
+                                view.error_message = Some(err.to_string());
+                            }
+                        }
+                        // Nothing to move past the first or last bookmark.
+                        KeyCode::Up | KeyCode::Down if shift => {}
+                        KeyCode::Up => {
+                            if view.selected_bookmark > 0 {
+                                view.selected_bookmark -= 1;
+                            }
+                        }
 
 
-                &words,
+                        KeyCode::Down => {
 
 
-                total_words,
+                            if view.selected_bookmark < reader.bookmarks().len() {
 
 
-                speed,
+                                view.selected_bookmark += 1;
 
 
-                words_read,
+                            }
 
 
-                reading_time,
+                        }
 
 
-                preferences_mode,
+                        KeyCode::Enter => {
 
 
-                bookmark_mode,
+                            if view.selected_bookmark == 0 {
+                                if reader.add_bookmark().is_none() {
+                                    view.error_message = Some(format!(
+                                        "This book already has {} bookmarks, the most allowed by max_bookmarks_per_book. Delete one first.",
+                                        reader.max_bookmarks()
+                                    ));
+                                } else {
+                                    settings.books.entry(book.clone()).or_default().remember(&reader);
+                                    if let Err(err) = settings.save() {
+                                        view.error_message = Some(err.to_string());
+                                    }
+                                }
+                            } else {
 
 
-                pause_mode,
+                                let bookmark = &reader.bookmarks()[view.selected_bookmark - 1];
+                                if bookmark.lost {
+                                    view.error_message = Some(format!(
+                                        "The text of bookmark \"{}\" is no longer in this document.",
+                                        bookmark.preview
+                                    ));
+                                } else {
+                                    reader.seek(bookmark.position);
+                                }
 
 
-                &bookmarks_list,
+                            }
 
 
-                selected_bookmark,
+                            view.bookmark_mode = false;
 
 
-                &file_path,
+                        }
 
 
-                smart_mode,
+                        KeyCode::Char('r') | KeyCode::Char('n') if view.selected_bookmark > 0 => {
+                            let bookmark = &reader.bookmarks()[view.selected_bookmark - 1];
+                            let edited = if code == KeyCode::Char('r') {
+                                utilities::get_text_ui("Bookmark name", &bookmark.name)
+                            } else {
+                                utilities::get_text_ui("Bookmark note", &bookmark.note)
+                            };
+                            if let Some(text) = edited {
+                                let bookmark = &mut reader.bookmarks_mut()[view.selected_bookmark - 1];
+                                let text = text.trim().to_string();
+                                if code == KeyCode::Char('r') {
+                                    bookmark.name = text;
+                                } else {
+                                    bookmark.note = text;
+                                }
+                                settings.books.entry(book.clone()).or_default().remember(&reader);
+                                if let Err(err) = settings.save() {
+                                    view.error_message = Some(err.to_string());
+                                }
+                            }
+                            terminal.clear().unwrap();
+                            terminal::enable_raw_mode().unwrap();
+                        }
+                        KeyCode::Char('x') | KeyCode::Delete if view.selected_bookmark > 0 => {
+                            reader.bookmarks_mut().remove(view.selected_bookmark - 1);
+                            view.selected_bookmark = view.selected_bookmark.min(reader.bookmarks().len());
+                            settings.books.entry(book.clone()).or_default().remember(&reader);
+                            if let Err(err) = settings.save() {
+                                view.error_message = Some(err.to_string());
+                            }
+                        }
+                        KeyCode::Esc => view.bookmark_mode = false,
+                        _ => {}
+                    }
+                } else if view.toc_mode {
+                    match code {
+                        KeyCode::Up => view.selected_chapter = view.selected_chapter.saturating_sub(1),
+                        KeyCode::Down if view.selected_chapter + 1 < reader.document().sections.len() => {
+                            view.selected_chapter += 1;
+                        }
+                        KeyCode::PageUp => view.selected_chapter = view.selected_chapter.saturating_sub(10),
+                        KeyCode::PageDown => {
+                            view.selected_chapter = (view.selected_chapter + 10).min(reader.document().sections.len().saturating_sub(1));
+                        }
+                        KeyCode::Enter => {
+                            if let Some(start) = reader.document().sections.get(view.selected_chapter).map(|section| section.tokens.start) {
+                                reader.seek(start);
+                            }
+                            view.toc_mode = false;
+                        }
+                        KeyCode::Esc | KeyCode::Char('c') => view.toc_mode = false,
+                        _ => {}
+                    }
+                } else if view.preferences_mode {
 
 
-                tts_enabled,
+                    match code {
 
 
-            )
+                        KeyCode::Up => reader.set_wpm(reader.wpm() + 10),
 
 
-        })
+                        KeyCode::Down => reader.set_wpm(reader.wpm().saturating_sub(10)),
 
 
-        .unwrap();
+                        KeyCode::Right => {
 
 
+                            if let DisplayMode::WordChunk(size) = reader.mode() {
 
 
+                                reader.set_mode(DisplayMode::WordChunk(size + 1));
 
-    loop {
 
+                            }
 
-        if event::poll(Duration::from_millis(10)).unwrap() {
 
+                        }
 
-            if let Event::Key(KeyEvent { code, .. }) = event::read().unwrap() {
 
+                        KeyCode::Left => {
 
-                if consume_next_event {
 
+                            if let DisplayMode::WordChunk(size) = reader.mode() {
 
-                    consume_next_event = false;
 
+                                reader.set_mode(DisplayMode::WordChunk(size.saturating_sub(1)));
 
-                    continue;
 
+                            }
 
-                }
 
+                        }
 
 
+                        KeyCode::Char('o') => view.pending_orp.enabled = !view.pending_orp.enabled,
 
 
-                if bookmark_mode {
+                        KeyCode::Char('f') => view.pending_orp.fixation_marks = !view.pending_orp.fixation_marks,
 
 
-                    match code {
+                        KeyCode::Char('g') => view.daily_goal = view.daily_goal.cycle(),
 
 
-                        KeyCode::Up => {
+                        KeyCode::Char('+') | KeyCode::Char('=') => view.daily_goal = view.daily_goal.increase(),
 
 
-                            if selected_bookmark > 0 {
+                        KeyCode::Char('-') => view.daily_goal = view.daily_goal.decrease(),
 
 
-                                selected_bookmark -= 1;
+                        KeyCode::Enter => {
 
 
+                            settings.global.speed = reader.wpm();
+                            if let DisplayMode::WordChunk(size) = reader.mode() {
+                                settings.global.chunk_size = size;
+                            }
+                            view.orp_config = view.pending_orp;
+                            settings.global.orp = view.orp_config;
+                            settings.global.daily_goal = view.daily_goal;
+                            if let Err(err) = settings.save() {
+                                view.error_message = Some(err.to_string());
                             }
 
 
-                        }
-
+                            view.preferences_mode = false;
 
-                        KeyCode::Down => {
 
+                        }
 
-                            if selected_bookmark < bookmarks_list.len() {
 
+                        KeyCode::Esc => view.preferences_mode = false,
 
-                                selected_bookmark += 1;
 
+                        _ => {}
 
-                            }
 
+                    }
 
-                        }
 
+                } else {
 
-                        KeyCode::Enter => {
 
+                    match code {
 
-                            if selected_bookmark == 0 {
 
+                        KeyCode::Char(' ') => {
 
-                                let preview = words
 
+                            reader.set_paused(!reader.is_paused());
 
-                                    .get(current_word_index..(current_word_index + 5).min(words.len()))
 
+                            if reader.is_paused() {
+                                view.session.pauses += 1;
 
-                                    .unwrap_or(&[])
 
+                                let _ = tts.stop();
 
-                                    .join(" ");
 
+                            }
 
-                                bookmarks_list.push((current_word_index, preview.clone()));
 
+                        }
 
 
+                        KeyCode::Char('p') => {
+                            view.pending_orp = view.orp_config;
+                            view.preferences_mode = true;
+                        }
 
 
-                                let file_path = file_path.clone();
+                        KeyCode::Esc => {
+                            if let Some(cancelled) = view.loading.take() {
+                                cancelled.cancel();
+                            }
+                        }
 
 
-                                let book_entry = book_data.entry(file_path.clone()).or_insert_with(|| {
+                        KeyCode::Char('d') => {
+                            // Include the session in progress, which is only saved on quit.
+                            let mut history = settings.history();
+                            history.extend(view.session.finish(reader.position()));
+                            view.dashboard = Some(ReadingStats::new(&history, settings, &view.daily_goal));
+                        }
 
 
-                                    json!({
+                        KeyCode::Char('m') => reader.set_smart_chunking(!reader.smart_chunking()),
 
 
-                                        "bookmarks": [],
+                        KeyCode::Char('t') => view.tts_enabled = !view.tts_enabled,
 
 
-                                        "speed": speed,
+                        KeyCode::Char('s') => {
 
 
-                                        "chunk_size": chunk_size,
+                            // Keep the reader's place: entering sentence mode backs up to the
+                            // start of the current sentence, leaving it resumes at that word.
+                            reader.set_mode(match reader.mode() {
+                                DisplayMode::Sentence => DisplayMode::WordChunk(reader.chunk_size()),
+                                DisplayMode::WordChunk(_) => DisplayMode::Sentence,
+                            });
 
 
-                                        "last_position": 0
+                        }
 
 
-                                    })
+                        KeyCode::Char('w') => {
+                            if let Some(url) = utilities::get_url_ui() {
+                                if let Some(previous) = view.loading.take() {
+                                    previous.cancel();
+                                }
+                                view.loading = Some(Loading::start(Source::Url(url), &cache, &settings.global.extract));
+                            }
+                            terminal.clear().unwrap();
+                            terminal::enable_raw_mode().unwrap();
+                        }
 
 
-                                });
+                        KeyCode::Char('l') => {
 
 
+                            let streak = goals::streak(&settings.history(), &view.daily_goal);
 
 
+                            if let Some(selected_file) =utilities::load_file_menu_ui(settings, streak) {
+                                // Keep reading the current document until the new one is ready.
+                                if let Some(previous) = view.loading.take() {
+                                    previous.cancel();
+                                }
+                                view.loading = Some(Loading::start(Source::from_location(selected_file), &cache, &settings.global.extract));
+                            }
+                            terminal.clear().unwrap();
+                            terminal::enable_raw_mode().unwrap();
 
-                                if !book_entry["bookmarks"].is_array() {
 
+                            terminal.draw(|f| draw_main_ui(f, &reader, &view)).unwrap();
 
-                                    book_entry["bookmarks"] = json!([]);
 
+                        }
 
-                                }
 
+                        KeyCode::Char('b') => {
 
 
+                            if view.bookmark_mode {
 
 
-                                if let Some(bookmarks) = book_entry["bookmarks"].as_array_mut() {
+                                view.bookmark_mode = false;
 
 
-                                    bookmarks.push(json!({ "position": current_word_index, "preview": preview.clone() }));
+                            } else {
 
 
-                                }
+                                view.bookmark_mode = true;
 
 
-                                if let DisplayMode::WordChunk(size) = display_mode {
+                                view.selected_bookmark = 0;
 
 
-                                    save_settings(speed, size, book_data.clone(), None, None);
+                            }
 
 
-                                } else {
+                        }
 
 
-                                    save_settings(speed, 1, book_data.clone(), None, None);
+                        KeyCode::Char('c') => {
+                            view.toc_mode = true;
+                            view.selected_chapter = reader.document().section_at(reader.position()).unwrap_or(0);
+                        }
 
 
+                        KeyCode::Char('g') => {
+                            let last = reader.document().pages.last().map(|page| page.number);
+                            if let Some(last) = last {
+                                let page = utilities::get_text_ui(&format!("Go to page (1-{})", last), "");
+                                if let Some(page) = page.as_deref().map(str::trim).filter(|page| !page.is_empty()) {
+                                    match page.parse().ok().and_then(|page| reader.document().page_start(page)) {
+                                        Some(start) => reader.seek(start),
+                                        None => view.error_message = Some(format!("There is no page {}", page)),
+                                    }
                                 }
-
-
+                                terminal.clear().unwrap();
+                                terminal::enable_raw_mode().unwrap();
                             } else {
+                                view.error_message = Some("This document has no pages".to_string());
+                            }
+                        }
 
 
-                                current_word_index = bookmarks_list[selected_bookmark - 1].0;
-
-
+                        KeyCode::Char('r') => {
+                            // Reload the page the other way, keeping the choice for later pages.
+                            let source = Source::from_location(view.file_path.clone());
+                            let is_html = matches!(source, Source::Url(_))
+                                || view.file_path.ends_with(".html")
+                                || view.file_path.ends_with(".htm");
+                            if is_html {
+                                let extract = &mut settings.global.extract;
+                                extract.html = match extract.html {
+                                    HtmlMode::Article => HtmlMode::Full,
+                                    HtmlMode::Full => HtmlMode::Article,
+                                };
+                                if let Err(err) = settings.save() {
+                                    view.error_message = Some(err.to_string());
+                                }
+                                if let Some(previous) = view.loading.take() {
+                                    previous.cancel();
+                                }
+                                view.loading = Some(Loading::start(source, &cache, &settings.global.extract));
+                            } else {
+                                view.error_message = Some("Only web pages can switch between the article and the full page".to_string());
                             }
+                        }
 
 
-                            bookmark_mode = false;
+                        KeyCode::Char(']') => {
+                            reader.next_section();
+                        }
 
 
+                        KeyCode::Char('[') => {
+                            reader.previous_section();
                         }
 
 
-                        KeyCode::Esc => bookmark_mode = false,
+                        KeyCode::Char('q') => {
+                            let _ = tts.stop();
 
 
-                        _ => {}
+                            terminal::disable_raw_mode().unwrap();
 
 
-                    }
+                            terminal.backend_mut().execute(LeaveAlternateScreen).unwrap();
 
 
-                } else if preferences_mode {
+                            terminal.clear().unwrap();
 
 
-                    match code {
+                            break;
 
 
-                        KeyCode::Up => speed += 10,
+                        }
 
 
-                        KeyCode::Down => speed = (speed.saturating_sub(10)).max(1),
+                        KeyCode::Up => {
 
 
-                        KeyCode::Right => {
+                            reader.set_wpm(reader.wpm() + 10);
 
 
-                            if let DisplayMode::WordChunk(size) = &mut display_mode {
+                        }
 
 
-                                *size += 1;
+                        KeyCode::Down => {
 
 
-                            }
+                            reader.set_wpm(reader.wpm().saturating_sub(10));
 
 
                         }
 
 
-                        KeyCode::Left => {
+                        KeyCode::PageUp => {
 
 
-                            if let DisplayMode::WordChunk(size) = &mut display_mode {
+                            reader.set_wpm(reader.wpm() + 100);
 
 
-                                *size = size.saturating_sub(1);
+                        }
 
 
-                            }
+                        KeyCode::PageDown => {
 
 
-                        }
+                            reader.set_wpm(reader.wpm().saturating_sub(100));
 
 
-                        KeyCode::Enter => {
+                        }
 
 
-                            word_delay = Duration::from_millis(60000 / speed);
+                        KeyCode::Right => {
 
 
-                            if let DisplayMode::WordChunk(size) = display_mode {
+                            reader.skip_forward();
 
 
-                                save_settings(speed, size, book_data.clone(), None, None);
+                        }
 
 
-                            } else {
+                        KeyCode::Left => {
 
 
-                                save_settings(speed, 1, book_data.clone(), None, None);
+                            reader.skip_back();
 
 
-                            }
+                        }
 
 
-                            preferences_mode = false;
+                        KeyCode::Char(c) if c.is_digit(10) => {
 
 
-                        }
+                            reader.set_mode(DisplayMode::WordChunk(c.to_digit(10).unwrap() as usize));
 
 
-                        KeyCode::Esc => preferences_mode = false,
+                        }
 
 
                         _ => {}
@@ -679,677 +1162,112 @@ pub fn run_ui(
                     }
 
 
-                } else {
+                }
 
 
-                    match code {
 
 
-                        KeyCode::Char(' ') => {
 
+                terminal.draw(|f| draw_main_ui(f, &reader, &view)).unwrap();
 
-                            pause_mode = !pause_mode;
 
+            }
 
-                            if pause_mode {
 
+        }
 
-                                let _ = tts.stop();
 
 
-                            }
 
 
-                        }
+        // The reader only moves on while the text is on screen.
 
 
-                        KeyCode::Char('p') => preferences_mode = true,
+        let step = if view.preferences_mode || view.dashboard.is_some() || view.error_message.is_some() {
+            None
+        } else {
+            reader.tick(Instant::now())
+        };
 
 
-                        KeyCode::Char('m') => smart_mode = !smart_mode,
+        if let Some(step) = step {
 
 
-                        KeyCode::Char('t') => tts_enabled = !tts_enabled,
+            view.words_read += step.words;
 
 
-                        KeyCode::Char('s') => {
+            view.reading_time += step.duration.as_secs_f64();
+            view.session.words_read += step.words;
+            view.session.reading_time += step.duration.as_secs_f64();
+            if !goal_announced && view.goal_progress.is_met(&view.daily_goal, &view.session) {
+                goal_announced = true;
+                view.goal_notice_until = Some(Instant::now() + Duration::from_secs(5));
+            }
 
 
-                            display_mode = if display_mode == DisplayMode::Sentence {
 
 
-                                DisplayMode::WordChunk(1)
 
+            // At the end of the text nothing is spoken; the user can still quit or load a new file.
 
-                            } else {
 
+            if view.tts_enabled && !reader.is_finished() {
 
-                                DisplayMode::Sentence
 
+                let _ = tts.speak(reader.current_frame().text(), true);
 
-                            };
 
+            }
+        }
 
-                            words = match display_mode {
 
 
-                                DisplayMode::WordChunk(_) => utilities::read_file_content(&file_path),
 
 
-                                DisplayMode::Sentence => utilities::read_file_sentences(&file_path),
+        // Swap in a document loaded in the background once it is ready.
 
 
-                            };
+        let mut loading_changed = view.loading.as_mut().is_some_and(Loading::changed);
+        if let Some(result) = view.loading.as_ref().and_then(Loading::try_finish) {
+            let source = view.loading.take().unwrap().source;
+            match result {
+                Ok(Loaded { document, fingerprint, cache_error }) => {
+                    let mut saved = cache_error.map_or(Ok(()), Err);
+                    if let Some(finished) = view.session.finish(reader.position()) {
+                        saved = saved.and(settings.record_session(finished));
+                    }
+                    settings.books.entry(book).or_default().remember(&reader);
+                    view.file_path = source.location().to_string();
+                    book = settings.open_book(&document, fingerprint, &view.file_path);
+                    reader.open(document);
+                    settings.books[&book].restore(&mut reader, &settings.global);
+                    if let Err(err) = saved.and(settings.save()) {
+                        view.error_message = Some(err.to_string());
+                    }
+                    view.session = SessionTracker::start(&book, &view.file_path, reader.position());
+                    view.goal_progress = GoalProgress::new(&settings.history());
+                }
+                Err(ExtractError::Cancelled { .. }) => {}
+                // Keep reading the current document.
+                Err(e) => view.error_message = Some(e.to_string()),
+            }
+            loading_changed = true;
+        }
 
 
-                            total_words = words.len();
 
 
-                            current_word_index = 0;
 
+        if step.is_some() || loading_changed {
 
-                        }
 
 
-                        KeyCode::Char('w') => {
-                            if let Some(url) = utilities::get_url_ui() {
-                                if let Ok(content) = utilities::get_content_from_url(&url) {
-                                    words = content.split_whitespace().map(String::from).collect();
-                                    total_words = words.len();
-                                    current_word_index = 0;
-                                    file_path = url;
-                                }
-                            }
-                            terminal.clear().unwrap();
-                            terminal::enable_raw_mode().unwrap();
-                        }
 
 
-                        KeyCode::Char('l') => {
+            terminal.draw(|f| draw_main_ui(f, &reader, &view)).unwrap();
 
 
-                            match utilities::load_file_menu_ui(book_data) {
-
-
-                                Some(selected_file) => {
-
-
-                                    words = utilities::read_file_content(&selected_file);
-
-
-                                    total_words = words.len();
-
-
-                                    file_path = selected_file.clone();
-
-
-
-
-
-                                    let book_entry =
-
-
-                                        book_data.entry(selected_file.clone()).or_insert_with(|| {
-
-
-                                            json!({
-
-
-                                                "bookmarks": [],
-
-
-                                                "speed": global_speed,
-
-
-                                                "chunk_size": global_chunk_size,
-
-
-                                                "last_position": 0
-
-
-                                            })
-
-
-                                        });
-
-
-
-
-
-                                    speed = book_entry["speed"].as_u64().unwrap_or(global_speed);
-
-
-                                    chunk_size = book_entry["chunk_size"].as_u64().unwrap_or(global_chunk_size as u64) as usize;
-
-
-                                    display_mode = DisplayMode::WordChunk(chunk_size);
-
-
-                                    current_word_index = book_entry["last_position"].as_u64().unwrap_or(0) as usize;
-
-
-
-
-
-                                    bookmarks_list.clear();
-
-
-                                    if let Some(bookmarks) = book_entry["bookmarks"].as_array() {
-
-
-                                        bookmarks_list.extend(bookmarks.iter().filter_map(|bm| {
-
-
-                                            Some((
-
-
-                                                bm.get("position")?.as_u64()? as usize,
-
-
-                                                bm.get("preview")?.as_str()?.to_string(),
-
-
-                                            ))
-
-
-                                        }));
-
-
-                                    }
-
-
-                                }
-
-
-                                None => {}
-
-
-                            }
-                            terminal.clear().unwrap();
-                            terminal::enable_raw_mode().unwrap();
-
-
-                            terminal
-
-
-                                .draw(|f| {
-
-
-                                    draw_main_ui(
-
-
-                                        f,
-
-
-                                        current_word_index,
-
-
-                                        display_mode,
-
-
-                                        &words,
-
-
-                                        total_words,
-
-
-                                        speed,
-
-
-                                        words_read,
-
-
-                                        reading_time,
-
-
-                                        preferences_mode,
-
-
-                                        bookmark_mode,
-
-
-                                        pause_mode,
-
-
-                                        &bookmarks_list,
-
-
-                                        selected_bookmark,
-
-
-                                        &file_path,
-
-
-                                        smart_mode,
-
-
-                                        tts_enabled,
-
-
-                                    )
-
-
-                                })
-
-
-                                .unwrap();
-
-
-                        }
-
-
-                        KeyCode::Char('b') => {
-
-
-                            if bookmark_mode {
-
-
-                                bookmark_mode = false;
-
-
-                            } else {
-
-
-                                bookmark_mode = true;
-
-
-                                selected_bookmark = 0;
-
-
-                            }
-
-
-                        }
-
-
-                        KeyCode::Char('q') => {
-
-
-                            let _ = tts.stop();
-
-
-                            terminal::disable_raw_mode().unwrap();
-
-
-                            terminal.backend_mut().execute(LeaveAlternateScreen).unwrap();
-
-
-                            terminal.clear().unwrap();
-
-
-                            break;
-
-
-                        }
-
-
-                        KeyCode::Up => {
-
-
-                            speed += 10;
-
-
-                            word_delay = Duration::from_millis(60000 / speed);
-
-
-                        }
-
-
-                        KeyCode::Down => {
-
-
-                            speed = (speed.saturating_sub(10)).max(1);
-
-
-                            word_delay = Duration::from_millis(60000 / speed);
-
-
-                        }
-
-
-                        KeyCode::PageUp => {
-
-
-                            speed += 100;
-
-
-                            word_delay = Duration::from_millis(60000 / speed);
-
-
-                        }
-
-
-                        KeyCode::PageDown => {
-
-
-                            speed = (speed.saturating_sub(100)).max(1);
-
-
-                            word_delay = Duration::from_millis(60000 / speed);
-
-
-                        }
-
-
-                        KeyCode::Right => {
-
-
-                            if let DisplayMode::WordChunk(size) = display_mode {
-
-
-                                current_word_index = (current_word_index + size).min(words.len());
-
-
-                            } else {
-
-
-                                current_word_index = (current_word_index + 1).min(words.len());
-
-
-                            }
-
-
-                        }
-
-
-                        KeyCode::Left => {
-
-
-                            if let DisplayMode::WordChunk(size) = display_mode {
-
-
-                                current_word_index = current_word_index.saturating_sub(size);
-
-
-                            } else {
-
-
-                                current_word_index = current_word_index.saturating_sub(1);
-
-
-                            }
-
-
-                        }
-
-
-                        KeyCode::Char(c) if c.is_digit(10) => {
-
-
-                            display_mode = DisplayMode::WordChunk(c.to_digit(10).unwrap() as usize);
-
-
-                        }
-
-
-                        _ => {}
-
-
-                    }
-
-
-                }
-
-
-
-
-
-                terminal
-
-
-                    .draw(|f| {
-
-
-                        draw_main_ui(
-
-
-                            f,
-
-
-                            current_word_index,
-
-
-                            display_mode,
-
-
-                            &words,
-
-
-                            total_words,
-
-
-                            speed,
-
-
-                            words_read,
-
-
-                            reading_time,
-
-
-                            preferences_mode,
-
-
-                            bookmark_mode,
-
-
-                            pause_mode,
-
-
-                            &bookmarks_list,
-
-
-                            selected_bookmark,
-
-
-                            &file_path,
-
-
-                            smart_mode,
-
-
-                            tts_enabled,
-
-
-                        )
-
-
-                    })
-
-
-                    .unwrap();
-
-
-            }
-
-
-        }
-
-
-
-
-
-        if !pause_mode && !preferences_mode && last_update.elapsed() >= word_delay {
-
-
-            last_update = Instant::now();
-
-
-            if current_word_index < words.len() {
-
-
-                let advance_chunk_size = if smart_mode {
-
-
-                    match display_mode {
-
-
-                        DisplayMode::WordChunk(chunk_size) => get_adaptive_chunk_size(&words, current_word_index, chunk_size),
-
-
-                        DisplayMode::Sentence => 1, // TODO
-
-
-                    }
-
-
-                } else {
-
-
-                    match display_mode {
-
-
-                        DisplayMode::WordChunk(chunk_size) => chunk_size,
-
-
-                        DisplayMode::Sentence => 1, // TODO
-
-
-                    }
-
-
-                };
-
-
-                current_word_index += advance_chunk_size;
-
-
-                words_read += advance_chunk_size;
-
-
-                reading_time += word_delay.as_secs_f64();
-
-
-
-
-
-                if tts_enabled {
-
-
-                    let word_display = match display_mode {
-
-
-                        DisplayMode::WordChunk(chunk_size) => {
-
-
-                            let display_chunk_size = if smart_mode {
-
-
-                                get_adaptive_chunk_size(&words, current_word_index, chunk_size)
-
-
-                            } else {
-
-
-                                chunk_size
-
-
-                            };
-
-
-                            words[current_word_index..current_word_index + display_chunk_size.min(words.len() - current_word_index)]
-
-
-                                .join(" ")
-
-
-                        }
-
-
-                        DisplayMode::Sentence => words[current_word_index].clone(),
-
-
-                    };
-
-
-                    let _ = tts.speak(word_display, true);
-
-
-                }
-
-
-            } else {
-
-
-                // Don't break here. Just stop advancing.
-
-
-                // The user can still quit or load a new file.
-
-
-            }
-
-
-
-
-
-            terminal
-
-
-                .draw(|f| {
-
-
-                    draw_main_ui(
-
-
-                        f,
-
-
-                        current_word_index,
-
-
-                        display_mode,
-
-
-                        &words,
-
-
-                        total_words,
-
-
-                        speed,
-
-
-                        words_read,
-
-
-                        reading_time,
-
-
-                        preferences_mode,
-
-
-                        bookmark_mode,
-
-
-                        pause_mode,
-
-
-                        &bookmarks_list,
-
-
-                        selected_bookmark,
-
-
-                        &file_path,
-
-
-                        smart_mode,
-
-
-                        tts_enabled,
-
-
-                    )
-
-
-                })
-
-
-                .unwrap();
-
-
-        }
+        }
 
 
     }
@@ -1376,7 +1294,16 @@ pub fn run_ui(
 
 
 
-    return current_word_index;
+    // The terminal is back to normal, so errors can go to stderr again.
+    if let Some(finished) = view.session.finish(reader.position()) {
+        if let Err(err) = settings.record_session(finished) {
+            eprintln!("{}", err);
+        }
+    }
+    settings.books.entry(book).or_default().remember(&reader);
+    if let Err(err) = settings.save() {
+        eprintln!("{}", err);
+    }
 
 
 }
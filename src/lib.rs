@@ -0,0 +1,28 @@
+//! The RSVP reading engine, usable without the terminal interface.
+//!
+//! Load a [`Document`](document::Document) with one of the [`extractors`] (or
+//! [`Document::from_text`](document::Document::from_text)), wrap it in a
+//! [`Reader`](reader::Reader) and call `tick` from your own event loop:
+//!
+//! ```no_run
+//! use rsvp::document::Document;
+//! use rsvp::reader::Reader;
+//! use rsvp::timing::TimingConfig;
+//! use std::time::{Duration, Instant};
+//!
+//! let mut reader = Reader::new(Document::from_text("Hello there, reader."), TimingConfig::default());
+//! reader.set_wpm(400);
+//! println!("{}", reader.current_frame().text());
+//! while !reader.is_finished() {
+//!     if reader.tick(Instant::now()).is_some() && !reader.is_finished() {
+//!         println!("{}", reader.current_frame().text());
+//!     }
+//!     std::thread::sleep(Duration::from_millis(10));
+//! }
+//! ```
+
+pub mod document;
+pub mod extractors;
+pub mod orp;
+pub mod reader;
+pub mod timing;
@@ -0,0 +1,142 @@
+//! Loads documents on a worker thread, so the current book stays readable
+//! while a large PDF is extracted or a web page downloads.
+
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use rsvp::document::Document;
+use rsvp::extractors::{ExtractError, ExtractOptions, Progress};
+
+use crate::cache::Cache;
+use crate::utilities;
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_FRAME_MS: u128 = 100;
+
+/// Where a document is loaded from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    File(String),
+    Url(String),
+}
+
+impl Source {
+    /// A web page for `http://` and `https://` locations, otherwise a file.
+    pub fn from_location(location: String) -> Source {
+        if location.starts_with("http://") || location.starts_with("https://") {
+            Source::Url(location)
+        } else {
+            Source::File(location)
+        }
+    }
+
+    /// The file path or URL.
+    pub fn location(&self) -> &str {
+        match self {
+            Source::File(path) => path,
+            Source::Url(url) => url,
+        }
+    }
+    /// A short name to show while loading: the file name, or the URL.
+    pub fn name(&self) -> String {
+        match self {
+            Source::File(path) => Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone()),
+            Source::Url(url) => url.clone(),
+        }
+    }
+}
+
+/// A loaded document, ready to open.
+pub struct Loaded {
+    pub document: Document,
+    /// The document's [`Document::fingerprint`], worked out while loading.
+    pub fingerprint: String,
+    /// Why the document could not be cached, when it could not.
+    pub cache_error: Option<io::Error>,
+}
+
+/// Loads `source` on the calling thread, going through `cache` for files.
+pub fn load(source: &Source, cache: &Cache, options: &ExtractOptions, progress: &Progress) -> Result<Loaded, ExtractError> {
+    let (document, cache_error) = match source {
+        Source::File(path) => cache.get_content(path, options, progress)?,
+        Source::Url(url) => (utilities::get_content_from_url(url, options)?, None),
+    };
+    let fingerprint = document.fingerprint();
+    Ok(Loaded { document, fingerprint, cache_error })
+}
+
+/// A document being loaded in the background.
+pub struct Loading {
+    pub source: Source,
+    progress: Arc<Progress>,
+    result: Receiver<Result<Loaded, ExtractError>>,
+    started: Instant,
+    /// Spinner frame and progress as last drawn.
+    shown: Option<(usize, (usize, usize))>,
+}
+
+impl Loading {
+    /// Starts loading `source`, going through `cache` for files.
+    pub fn start(source: Source, cache: &Cache, options: &ExtractOptions) -> Loading {
+        let progress = Arc::new(Progress::default());
+        let (sender, result) = mpsc::channel();
+
+        let worker_source = source.clone();
+        let worker_progress = Arc::clone(&progress);
+        let cache = cache.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            // Nobody is listening any more if loading was cancelled.
+            let _ = sender.send(load(&worker_source, &cache, &options, &worker_progress));
+        });
+
+        Loading { source, progress, result, started: Instant::now(), shown: None }
+    }
+
+    /// Returns the result once the worker has finished.
+    pub fn try_finish(&self) -> Option<Result<Loaded, ExtractError>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            // The extractor panicked, which only malformed files make it do.
+            Err(TryRecvError::Disconnected) => Some(Err(ExtractError::corrupt(
+                &self.source.name(),
+                "the file could not be read",
+            ))),
+        }
+    }
+
+    /// Asks the worker to stop at its next page or chapter. Loads that
+    /// cannot be interrupted run to the end and are thrown away.
+    pub fn cancel(self) {
+        self.progress.cancel();
+    }
+
+    /// Steps finished and the total, which is 0 when unknown.
+    pub fn progress(&self) -> (usize, usize) {
+        self.progress.get()
+    }
+
+    pub fn spinner(&self) -> char {
+        SPINNER[self.spinner_frame()]
+    }
+
+    fn spinner_frame(&self) -> usize {
+        (self.started.elapsed().as_millis() / SPINNER_FRAME_MS) as usize % SPINNER.len()
+    }
+
+    /// Whether anything shown on screen has changed since the last call.
+    pub fn changed(&mut self) -> bool {
+        let now = Some((self.spinner_frame(), self.progress()));
+        let changed = self.shown != now;
+        self.shown = now;
+        changed
+    }
+}
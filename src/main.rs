@@ -1,17 +1,24 @@
+mod cache;
+mod goals;
 mod interface;
+mod loader;
+mod paths;
+mod reading_session;
+mod settings;
+mod stats;
 mod utilities;
-use clap::{Arg, Command};
-use crate::utilities::{load_settings, save_settings, read_file_content, read_file_sentences};
-use crate::interface::DisplayMode;
-use serde_json::json;
+use clap::{Arg, ArgAction, Command};
+use crate::settings::Settings;
+use crate::cache::Cache;
+use crate::loader::{Loaded, Source};
+use rsvp::extractors::Progress;
+use rsvp::reader::{DisplayMode, Reader};
 use std::fs;
 use std::path::Path;
+use std::process;
 
 
 fn main() {
-    // Load settings from JSON
-    let (global_speed, global_chunk_size, mut book_data) = load_settings(); // ✅ Fix: Ensure book_data is loaded
-
     let matches = Command::new("RSVP")
         .version("1.2.0")
         .author("Your Name <you@example.com>")
@@ -37,86 +44,95 @@ fn main() {
                 .num_args(1)
                 .help("Number of words per chunk (overrides saved preference)"),
         )
+        .arg(
+            Arg::new("encoding")
+                .long("encoding")
+                .num_args(1)
+                .value_name("NAME")
+                .help("Read the input text file in this encoding, e.g. latin1 or utf-16le, instead of detecting it"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .num_args(1)
+                .value_name("PATH")
+                .help("Use this config file, keeping books and cache next to it"),
+        )
+        .arg(
+            Arg::new("clear_cache")
+                .long("clear-cache")
+                .action(ArgAction::SetTrue)
+                .help("Delete cached documents, then exit unless --input is given"),
+        )
         .get_matches();
 
-    let input_file = matches.get_one::<String>("input").cloned()
-        .or_else(|| utilities::load_file_menu_ui(&book_data));
-
-    if let Some(file_path) = input_file {
-        let absolute_path = fs::canonicalize(Path::new(&file_path))
-            .unwrap_or_else(|_| Path::new(&file_path).to_path_buf());
-
-        let absolute_path_str = absolute_path.to_string_lossy().to_string();
-
-        let book_settings = book_data.entry(absolute_path_str.clone()).or_insert_with(|| json!({
-            "bookmarks": [],
-            "speed": global_speed,
-            "chunk_size": global_chunk_size,
-            "last_position": 0
-        }));
+    let mut settings = Settings::load(matches.get_one::<String>("config").map(Path::new));
 
-        let chunk_size = matches
-            .get_one::<String>("chunk_size")
-            .and_then(|cs| cs.parse().ok())
-            .or_else(|| book_settings.as_object().and_then(|b| b.get("chunk_size")?.as_u64()).map(|cs| cs as usize))
-            .unwrap_or(global_chunk_size);
+    if matches.get_flag("clear_cache") {
+        if let Some(cache_dir) = settings.cache_dir() {
+            if let Err(err) = cache::clear(cache_dir) {
+                eprintln!("Could not clear the cache: {}", err);
+                process::exit(1);
+            }
+            eprintln!("Cleared the document cache in {}", cache_dir.display());
+        }
+        if !matches.contains_id("input") {
+            return;
+        }
+    }
+    let cache = Cache::new(settings.cache_dir(), settings.global.max_cache_mb);
 
-        let display_mode = book_settings.as_object()
-            .and_then(|b| b.get("display_mode"))
-            .and_then(|dm| dm.as_str())
-            .map(|s| {
-                if s == "sentence" {
-                    DisplayMode::Sentence
-                } else {
-                    DisplayMode::WordChunk(chunk_size)
-                }
-            })
-            .unwrap_or(DisplayMode::WordChunk(chunk_size));
+    let input_file = matches.get_one::<String>("input").cloned()
+        .or_else(|| {
+            let streak = goals::streak(&settings.history(), &settings.global.daily_goal);
+            utilities::load_file_menu_ui(&mut settings, streak)
+        });
 
-        let words = match display_mode {
-            DisplayMode::WordChunk(_) => read_file_content(&absolute_path_str),
-            DisplayMode::Sentence => read_file_sentences(&absolute_path_str),
+    if let Some(file_path) = input_file {
+        let source = match Source::from_location(file_path) {
+            Source::File(path) => Source::File(
+                fs::canonicalize(Path::new(&path))
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or(path),
+            ),
+            url => url,
         };
-        let total_words = words.len();
-
-        let speed = matches
-            .get_one::<String>("speed")
-            .and_then(|s| s.parse().ok())
-            .or_else(|| book_settings.as_object().and_then(|b| b.get("speed")?.as_u64()))
-            .unwrap_or(global_speed);
+        let location = source.location().to_string();
 
-        let _last_position = book_settings
-            .as_object().and_then(|b| b.get("last_position")?.as_u64())
-            .unwrap_or(0) as usize;
+        // The encoding given on the command line is only for this file.
+        let mut options = settings.global.extract.clone();
+        if let Some(label) = matches.get_one::<String>("encoding") {
+            if encoding_rs::Encoding::for_label(label.trim().as_bytes()).is_none() {
+                eprintln!("Unknown encoding '{}'", label);
+                process::exit(1);
+            }
+            options.encoding = Some(label.clone());
+        }
 
-        // ✅ Fix: Ensure correct arguments to run_ui()
-        // let final_position = interface::run_ui(speed, chunk_size, total_words, words, &mut book_data, absolute_path_str.clone());        
-        let final_position = interface::run_ui(
-            speed,
-            display_mode,
-            total_words,
-            words,
-            &mut book_data,
-            global_speed,          // ✅ Add missing global_speed
-            global_chunk_size,     // ✅ Add missing global_chunk_size
-            absolute_path_str.clone(), // ✅ Correct file_path position
-            _last_position,
-        );
+        eprintln!("Loading {}...", location);
+        let Loaded { document, fingerprint, cache_error } = match loader::load(&source, &cache, &options, &Progress::default()) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+        if let Some(err) = cache_error {
+            eprintln!("{}", err);
+        }
+        let book = settings.open_book(&document, fingerprint, &location);
+        let mut reader = Reader::new(document, settings.global.timing.clone());
+        settings.books[&book].restore(&mut reader, &settings.global);
 
-        book_data.entry(absolute_path_str.clone()).or_insert_with(|| json!({}));
-        if let Some(book) = book_data.get_mut(&absolute_path_str) {
-            book["last_position"] = json!(final_position);
-            book["speed"] = json!(speed);
-            if let DisplayMode::WordChunk(size) = display_mode {
-                book["chunk_size"] = json!(size);
-            } else {
-                book["chunk_size"] = json!(global_chunk_size);
+        if let Some(speed) = matches.get_one::<String>("speed").and_then(|s| s.parse().ok()) {
+            reader.set_wpm(speed);
+        }
+        if let Some(chunk_size) = matches.get_one::<String>("chunk_size").and_then(|cs| cs.parse().ok()) {
+            if let DisplayMode::WordChunk(_) = reader.mode() {
+                reader.set_mode(DisplayMode::WordChunk(chunk_size));
             }
-            book["display_mode"] = match display_mode {
-                DisplayMode::Sentence => json!("sentence"),
-                DisplayMode::WordChunk(_) => json!("word_chunk"),
-            };
         }
-        save_settings(global_speed, global_chunk_size, book_data, None, None);
+
+        interface::run_ui(reader, &mut settings, book, location);
     }
 }
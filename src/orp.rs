@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+/// Optimal Recognition Point display settings.
+///
+/// Stored under `global.orp` in the settings file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OrpConfig {
+    /// Align each chunk on its pivot letter instead of centring it.
+    pub enabled: bool,
+    /// Draw fixation marks above and below the pivot column.
+    pub fixation_marks: bool,
+}
+
+/// A chunk of text split around its pivot letter.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OrpSplit<'a> {
+    pub before: &'a str,
+    pub pivot: &'a str,
+    pub after: &'a str,
+}
+
+/// Returns the pivot position, in letters, for a word of `length` letters.
+///
+/// The pivot sits slightly left of centre, where the eye recognises a word
+/// fastest.
+fn pivot_offset(length: usize) -> usize {
+    match length {
+        0 | 1 => 0,
+        2..=5 => 1,
+        6..=9 => 2,
+        10..=13 => 3,
+        _ => 4,
+    }
+}
+
+/// Splits `text` around its pivot letter.
+///
+/// For chunks of several words the pivot is taken from the word nearest the
+/// middle of the chunk, so the eye stays on the same column as the chunk
+/// changes. Only letters and digits are counted when placing the pivot,
+/// so "e-mail" pivots on its "m" rather than on the hyphen.
+pub fn split(text: &str) -> OrpSplit<'_> {
+    let middle = text.len() / 2;
+    let word = text
+        .split(char::is_whitespace)
+        .filter(|word| !word.is_empty())
+        .min_by_key(|word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            let centre = start + word.len() / 2;
+            centre.abs_diff(middle)
+        })
+        .unwrap_or(text);
+    let word_start = word.as_ptr() as usize - text.as_ptr() as usize;
+
+    let letters: Vec<(usize, char)> = word.char_indices().filter(|(_, c)| c.is_alphanumeric()).collect();
+    let Some(&(offset, pivot)) = letters.get(pivot_offset(letters.len())) else {
+        return OrpSplit { before: text, pivot: "", after: "" };
+    };
+
+    let start = word_start + offset;
+    let end = start + pivot.len_utf8();
+    OrpSplit {
+        before: &text[..start],
+        pivot: &text[start..end],
+        after: &text[end..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pivot(text: &str) -> (&str, &str, &str) {
+        let split = split(text);
+        (split.before, split.pivot, split.after)
+    }
+
+    #[test]
+    fn pivots_slightly_left_of_centre() {
+        assert_eq!(pivot("a"), ("", "a", ""));
+        assert_eq!(pivot("word"), ("w", "o", "rd"));
+        assert_eq!(pivot("reading"), ("re", "a", "ding"));
+    }
+
+    #[test]
+    fn skips_punctuation_inside_words() {
+        assert_eq!(pivot("e-mail"), ("e-", "m", "ail"));
+        assert_eq!(pivot("don't"), ("d", "o", "n't"));
+        assert_eq!(pivot("o'clock"), ("o'c", "l", "ock"));
+        assert_eq!(pivot("\"Hello,\""), ("\"H", "e", "llo,\""));
+    }
+
+    #[test]
+    fn pivots_on_the_middle_word_of_a_chunk() {
+        assert_eq!(pivot("the quick fox"), ("the q", "u", "ick fox"));
+    }
+
+    #[test]
+    fn leaves_text_without_letters_unsplit() {
+        assert_eq!(pivot("..."), ("...", "", ""));
+        assert_eq!(pivot(""), ("", "", ""));
+    }
+
+    #[test]
+    fn counts_characters_not_bytes() {
+        assert_eq!(pivot("café"), ("c", "a", "fé"));
+        assert_eq!(pivot("naïveté"), ("na", "ï", "veté"));
+    }
+}
@@ -0,0 +1,74 @@
+//! Where rsvp keeps its files, following the XDG base directory layout.
+
+use dirs_next::{cache_dir, config_dir, home_dir};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paths {
+    /// Preferences, edited by hand or from the preferences panel.
+    pub config_file: PathBuf,
+    /// Reading positions and bookmarks.
+    pub state_dir: PathBuf,
+    /// Data that can be rebuilt at any time.
+    pub cache_dir: PathBuf,
+}
+
+impl Paths {
+    /// `$XDG_CONFIG_HOME/rsvp/config.toml`, `$XDG_STATE_HOME/rsvp/` and
+    /// `$XDG_CACHE_HOME/rsvp/`, with the usual fallbacks under `~`.
+    pub fn standard() -> Option<Paths> {
+        let config = xdg_dir("XDG_CONFIG_HOME", config_dir)?;
+        let state = xdg_dir("XDG_STATE_HOME", || home_dir().map(|home| home.join(".local").join("state")))?;
+        let cache = xdg_dir("XDG_CACHE_HOME", cache_dir)?;
+        Some(Paths {
+            config_file: config.join("rsvp").join("config.toml"),
+            state_dir: state.join("rsvp"),
+            cache_dir: cache.join("rsvp"),
+        })
+    }
+
+    /// Keeps everything next to `config_file`, so each config file given
+    /// with `--config` is a separate profile with its own books and cache.
+    pub fn with_config(config_file: &Path) -> Paths {
+        let dir = config_file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Paths {
+            config_file: config_file.to_path_buf(),
+            cache_dir: dir.join("cache"),
+            state_dir: dir,
+        }
+    }
+
+    pub fn state_file(&self) -> PathBuf {
+        self.state_dir.join("state.json")
+    }
+
+    /// Every finished reading session, one JSON object per line.
+    pub fn history_file(&self) -> PathBuf {
+        self.state_dir.join("history.jsonl")
+    }
+
+    /// The single settings file used before the XDG layout.
+    pub fn legacy_file() -> Option<PathBuf> {
+        home_dir().map(|home| home.join(".rsvp_settings.json"))
+    }
+
+    pub fn create_dirs(&self) -> io::Result<()> {
+        if let Some(config_dir) = self.config_file.parent() {
+            fs::create_dir_all(config_dir)?;
+        }
+        fs::create_dir_all(&self.state_dir)?;
+        fs::create_dir_all(&self.cache_dir)
+    }
+}
+
+/// Reads an XDG base directory variable, ignoring relative paths as the
+/// specification requires.
+fn xdg_dir(var: &str, fallback: impl FnOnce() -> Option<PathBuf>) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(fallback)
+}
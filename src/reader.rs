@@ -0,0 +1,587 @@
+//! The reading engine: where the reader is in a document, how much is shown
+//! at once and when to move on. It knows nothing about terminals, so any
+//! front end can drive it by calling [`Reader::tick`] and drawing
+//! [`Reader::current_frame`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::document::{Document, Token};
+use crate::timing::{Timing, TimingConfig};
+
+/// Words shown in a bookmark's preview.
+const PREVIEW_WORDS: usize = 5;
+/// Words remembered on each side of a bookmark to find it again after the
+/// document changes.
+const CONTEXT_WORDS: usize = 8;
+/// How far a context word may have moved and still count as a match.
+const MAX_SHIFT: usize = 3;
+/// Share of the context that must be found for a bookmark to stay put or
+/// be moved to a new place.
+const MIN_MATCH: f64 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayMode {
+    WordChunk(usize),
+    Sentence,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmark {
+    pub position: usize,
+    pub preview: String,
+    /// A name given by the reader, shown instead of the preview.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+    /// The words just before the bookmark.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub context_before: String,
+    /// The words from the bookmark on.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub context_after: String,
+    /// Set when the bookmark's text could not be found in the document, so
+    /// `position` is probably wrong.
+    #[serde(skip)]
+    pub lost: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Bookmark {
+    /// A bookmark at word `position` of `document`, made now.
+    pub fn new(document: &Document, position: usize) -> Bookmark {
+        let mut bookmark = Bookmark { created: Some(Utc::now()), ..Bookmark::default() };
+        bookmark.place(document, position);
+        bookmark
+    }
+
+    /// The name, or the preview for unnamed bookmarks.
+    pub fn label(&self) -> &str {
+        if self.name.is_empty() { &self.preview } else { &self.name }
+    }
+
+    /// Moves the bookmark to word `position`, taking its preview and
+    /// context from there.
+    fn place(&mut self, document: &Document, position: usize) {
+        self.position = position;
+        self.preview = document.join(position..position + PREVIEW_WORDS);
+        self.context_before = document.join(position.saturating_sub(CONTEXT_WORDS)..position);
+        self.context_after = document.join(position..position + CONTEXT_WORDS);
+        self.lost = false;
+    }
+
+    /// Checks that the bookmark's text is still at its position in
+    /// `document`, and otherwise moves it to where that text is now, or
+    /// marks it as [`lost`](Self::lost). Bookmarks saved without context
+    /// are checked against their preview.
+    ///
+    /// `words` are the document's tokens after [`normalize`].
+    fn anchor(&mut self, document: &Document, words: &[String]) {
+        let after = if self.context_after.is_empty() { &self.preview } else { &self.context_after };
+        let context = Context::new(&self.context_before, after);
+        let wanted = context.before.len() + context.after.len();
+        if wanted == 0 {
+            self.position = self.position.min(document.len());
+            return;
+        }
+        let needed = (wanted as f64 * MIN_MATCH).ceil() as usize;
+
+        let (_, exact) = context.score(words, self.position);
+        if exact >= needed {
+            self.lost = false;
+            return;
+        }
+
+        // The best match, preferring words exactly in place over words
+        // close by, then the place nearest the old position for text that
+        // repeats.
+        let best = context
+            .candidates(words, needed.div_ceil(2))
+            .into_iter()
+            .map(|position| (context.score(words, position), position))
+            .filter(|((found, _), _)| *found >= needed)
+            .min_by_key(|(score, position)| (Reverse(*score), position.abs_diff(self.position)));
+
+        match best {
+            Some((_, position)) => self.place(document, position),
+            None => {
+                self.position = self.position.min(document.len());
+                self.lost = true;
+            }
+        }
+    }
+}
+
+/// A bookmark's surrounding words, normalized for comparison.
+struct Context {
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+impl Context {
+    fn new(before: &str, after: &str) -> Context {
+        Context { before: normalize_all(before), after: normalize_all(after) }
+    }
+
+    /// How many context words are found around `position`, allowing each
+    /// to have moved by a few words, and how many are exactly in place.
+    fn score(&self, words: &[String], position: usize) -> (usize, usize) {
+        let expected = self
+            .before
+            .iter()
+            .enumerate()
+            .filter_map(|(i, word)| Some((word, (position + i).checked_sub(self.before.len())?)))
+            .chain(self.after.iter().enumerate().map(|(i, word)| (word, position + i)));
+
+        let (mut found, mut exact) = (0, 0);
+        for (word, index) in expected {
+            let start = index.saturating_sub(MAX_SHIFT).min(words.len());
+            let end = (index + MAX_SHIFT + 1).min(words.len());
+            if words[start..end].contains(word) {
+                found += 1;
+                if words.get(index) == Some(word) {
+                    exact += 1;
+                }
+            }
+        }
+        (found, exact)
+    }
+
+    /// Positions where at least `min_votes` context words would be exactly
+    /// in place: each word found in the document votes for where the
+    /// bookmark would be if that word were in place.
+    fn candidates(&self, words: &[String], min_votes: usize) -> Vec<usize> {
+        let mut offsets: HashMap<&str, Vec<isize>> = HashMap::new();
+        for (i, word) in self.before.iter().enumerate() {
+            offsets.entry(word).or_default().push((self.before.len() - i) as isize);
+        }
+        for (i, word) in self.after.iter().enumerate() {
+            offsets.entry(word).or_default().push(-(i as isize));
+        }
+
+        let mut votes: HashMap<usize, usize> = HashMap::new();
+        for (index, word) in words.iter().enumerate() {
+            let Some(offsets) = offsets.get(word.as_str()) else {
+                continue;
+            };
+            for offset in offsets {
+                if let Some(position) = index.checked_add_signed(*offset) {
+                    *votes.entry(position).or_default() += 1;
+                }
+            }
+        }
+        votes
+            .into_iter()
+            .filter(|(_, count)| *count >= min_votes)
+            .map(|(position, _)| position)
+            .collect()
+    }
+}
+
+/// Lowercases a word and drops punctuation, so re-extracted text with
+/// different quotes or dashes still matches.
+fn normalize(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// The normalized words of `text`, leaving out those that are only punctuation.
+fn normalize_all(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(normalize)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The words on screen at one moment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame<'a> {
+    /// Indices of the words shown; empty at the end of the text.
+    pub range: Range<usize>,
+    pub tokens: &'a [Token],
+}
+
+impl Frame<'_> {
+    pub fn text(&self) -> String {
+        self.tokens
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// What a call to [`Reader::tick`] moved past.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+    pub words: usize,
+    /// How long the frame was shown for.
+    pub duration: Duration,
+}
+
+/// A document being read, with its position, speed, display mode and
+/// bookmarks.
+pub struct Reader {
+    document: Document,
+    timing: Timing,
+    position: usize,
+    wpm: u64,
+    mode: DisplayMode,
+    /// Chunk size to return to when leaving sentence mode.
+    chunk_size: usize,
+    smart_chunking: bool,
+    paused: bool,
+    /// When the current frame was first shown, or `None` if it has not been
+    /// timed yet (after opening, seeking or resuming).
+    shown_at: Option<Instant>,
+    bookmarks: Vec<Bookmark>,
+    /// Most bookmarks [`add_bookmark`](Self::add_bookmark) allows; 0 for no limit.
+    max_bookmarks: usize,
+}
+
+impl Reader {
+    /// Opens `document` at the start, at 300 WPM, one word at a time.
+    pub fn new(document: Document, timing: TimingConfig) -> Reader {
+        Reader {
+            timing: Timing::new(timing, &document),
+            document,
+            position: 0,
+            wpm: 300,
+            mode: DisplayMode::WordChunk(1),
+            chunk_size: 1,
+            smart_chunking: false,
+            paused: false,
+            shown_at: None,
+            bookmarks: Vec::new(),
+            max_bookmarks: 0,
+        }
+    }
+
+    /// Switches to another document, keeping the speed and display settings,
+    /// starting from the beginning with no bookmarks.
+    pub fn open(&mut self, document: Document) {
+        self.timing = Timing::new(self.timing.config().clone(), &document);
+        self.document = document;
+        self.bookmarks.clear();
+        self.seek(0);
+    }
+
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Whether the whole document has been shown.
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.document.len()
+    }
+
+    /// Moves to word `index`, clamped to the end of the document.
+    pub fn seek(&mut self, index: usize) {
+        self.position = index.min(self.document.len());
+        self.shown_at = None;
+    }
+
+    /// Skips forward by one chunk, or one word in sentence mode.
+    pub fn skip_forward(&mut self) {
+        self.seek(self.position + self.skip_size());
+    }
+
+    /// Skips back by one chunk, or one word in sentence mode.
+    pub fn skip_back(&mut self) {
+        self.seek(self.position.saturating_sub(self.skip_size()));
+    }
+
+    fn skip_size(&self) -> usize {
+        match self.mode {
+            DisplayMode::WordChunk(size) => size,
+            DisplayMode::Sentence => 1,
+        }
+    }
+
+    pub fn next_section(&mut self) {
+        if let Some(start) = self.document.next_section_start(self.position) {
+            self.seek(start);
+        }
+    }
+
+    /// Restarts the current section, or goes to the previous one when
+    /// already at its start (see [`Document::previous_section_start`]).
+    pub fn previous_section(&mut self) {
+        if let Some(start) = self.document.previous_section_start(self.position) {
+            self.seek(start);
+        }
+    }
+
+    pub fn wpm(&self) -> u64 {
+        self.wpm
+    }
+
+    pub fn set_wpm(&mut self, wpm: u64) {
+        self.wpm = wpm.max(1);
+    }
+
+    pub fn mode(&self) -> DisplayMode {
+        self.mode
+    }
+
+    /// The chunk size used in word chunk mode, remembered while showing
+    /// sentences.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Changes the display mode. Entering sentence mode backs up to the
+    /// start of the current sentence so the reader keeps their place.
+    pub fn set_mode(&mut self, mode: DisplayMode) {
+        match mode {
+            DisplayMode::WordChunk(size) => self.chunk_size = size,
+            DisplayMode::Sentence if self.mode != DisplayMode::Sentence => {
+                self.seek(self.document.sentence_start(self.position));
+            }
+            DisplayMode::Sentence => {}
+        }
+        self.mode = mode;
+    }
+
+    pub fn smart_chunking(&self) -> bool {
+        self.smart_chunking
+    }
+
+    /// Sizes chunks by word length instead of a fixed word count.
+    pub fn set_smart_chunking(&mut self, enabled: bool) {
+        self.smart_chunking = enabled;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.shown_at = None;
+    }
+
+    /// The words to show now.
+    pub fn current_frame(&self) -> Frame<'_> {
+        let range = self.frame_range(self.position);
+        Frame {
+            tokens: &self.document.tokens[range.clone()],
+            range,
+        }
+    }
+
+    /// How long the current frame stays on screen at the current speed.
+    pub fn frame_duration(&self) -> Duration {
+        self.timing.duration(self.current_frame().tokens, self.wpm)
+    }
+
+    /// Moves to the next frame once the current one has been shown for long
+    /// enough. Call this regularly with the current time; it does nothing
+    /// while paused or at the end of the text.
+    pub fn tick(&mut self, now: Instant) -> Option<Step> {
+        if self.paused || self.is_finished() {
+            return None;
+        }
+        let shown_at = *self.shown_at.get_or_insert(now);
+        let duration = self.frame_duration();
+        if now.saturating_duration_since(shown_at) < duration {
+            return None;
+        }
+
+        let words = self.current_frame().range.len();
+        self.position += words;
+        self.shown_at = Some(now);
+        Some(Step { words, duration })
+    }
+
+    pub fn bookmarks(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Replaces the bookmarks, finding each one's text again in case the
+    /// document changed since they were made.
+    pub fn set_bookmarks(&mut self, mut bookmarks: Vec<Bookmark>) {
+        if !bookmarks.is_empty() {
+            let words: Vec<String> = self.document.tokens.iter().map(|token| normalize(&token.text)).collect();
+            for bookmark in &mut bookmarks {
+                bookmark.anchor(&self.document, &words);
+            }
+        }
+        self.bookmarks = bookmarks;
+    }
+
+    /// For renaming, annotating, removing and reordering bookmarks.
+    pub fn bookmarks_mut(&mut self) -> &mut Vec<Bookmark> {
+        &mut self.bookmarks
+    }
+
+    /// Bookmarks the current position and returns the new bookmark, or
+    /// `None` when the document already has
+    /// [`max_bookmarks`](Self::max_bookmarks).
+    pub fn add_bookmark(&mut self) -> Option<&Bookmark> {
+        if self.max_bookmarks > 0 && self.bookmarks.len() >= self.max_bookmarks {
+            return None;
+        }
+        self.bookmarks.push(Bookmark::new(&self.document, self.position));
+        self.bookmarks.last()
+    }
+
+    pub fn max_bookmarks(&self) -> usize {
+        self.max_bookmarks
+    }
+
+    /// Limits how many bookmarks can be added; 0 for no limit. Bookmarks
+    /// already there are kept even beyond the limit.
+    pub fn set_max_bookmarks(&mut self, limit: usize) {
+        self.max_bookmarks = limit;
+    }
+
+    /// Returns the range of words shown together as one unit at `index`.
+    fn frame_range(&self, index: usize) -> Range<usize> {
+        let document = &self.document;
+        let end = match self.mode {
+            DisplayMode::WordChunk(chunk_size) => {
+                let size = if self.smart_chunking {
+                    get_adaptive_chunk_size(&document.tokens, index, chunk_size)
+                } else {
+                    chunk_size
+                };
+                index + size.max(1)
+            }
+            DisplayMode::Sentence => document.sentence_end(index),
+        };
+        index.min(document.len())..end.min(document.len())
+    }
+}
+
+/// Adjusts the chunk size based on the length of the words.
+fn get_adaptive_chunk_size(
+    words: &[Token],
+    current_index: usize,
+    base_chunk_size: usize,
+) -> usize {
+    if current_index >= words.len() {
+        return base_chunk_size;
+    }
+
+    let mut total_chars = 0;
+    let mut num_words = 0;
+
+    // A simple heuristic: try to fit a certain number of characters in a chunk.
+    // Let's aim for an average of 5 characters per word in the base chunk size.
+    let target_chars = base_chunk_size * 5;
+
+    for i in 0..base_chunk_size * 2 { // Check up to twice the base chunk size
+        if current_index + i < words.len() {
+            total_chars += words[current_index + i].text.len();
+            num_words += 1;
+            if total_chars > target_chars {
+                break;
+            }
+        } else {
+            break;
+        }
+    }
+
+    num_words.max(1) // Always return at least 1.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "It was the best of times, it was the worst of times, it was the age of wisdom, \
+        it was the age of foolishness, it was the epoch of belief, it was the epoch of incredulity, \
+        it was the season of Light, it was the season of Darkness.";
+
+    fn words(document: &Document) -> Vec<String> {
+        document.tokens.iter().map(|token| normalize(&token.text)).collect()
+    }
+
+    /// A bookmark made at the word `at` of `TEXT`, anchored in `edited`.
+    fn anchored(at: &str, edited: &str) -> (Bookmark, Document) {
+        let original = Document::from_text(TEXT);
+        let position = original.tokens.iter().position(|token| token.text == at).unwrap();
+        let mut bookmark = Bookmark::new(&original, position);
+        let edited = Document::from_text(edited);
+        bookmark.anchor(&edited, &words(&edited));
+        (bookmark, edited)
+    }
+
+    #[test]
+    fn score_counts_words_in_place_and_nearby() {
+        let document = Document::from_text("a b c d e f g h");
+        let words = words(&document);
+        let context = Context::new("b c", "D. e");
+        assert_eq!(context.score(&words, 3), (4, 4));
+        assert_eq!(context.score(&words, 4), (4, 0));
+        assert_eq!(context.score(&words, 0), (2, 0));
+    }
+
+    #[test]
+    fn candidates_are_where_context_words_vote() {
+        let document = Document::from_text("x b c d e y b c d e");
+        let context = Context::new("b c", "d e");
+        let mut candidates = context.candidates(&words(&document), 4);
+        candidates.sort();
+        assert_eq!(candidates, [3, 8]);
+    }
+
+    #[test]
+    fn adds_bookmarks_up_to_the_limit() {
+        let mut reader = Reader::new(Document::from_text(TEXT), TimingConfig::default());
+        reader.set_max_bookmarks(2);
+        assert!(reader.add_bookmark().is_some());
+        reader.seek(5);
+        assert_eq!(reader.add_bookmark().map(|bookmark| bookmark.position), Some(5));
+        assert!(reader.add_bookmark().is_none());
+        assert_eq!(reader.bookmarks().len(), 2);
+
+        reader.set_max_bookmarks(0);
+        assert!(reader.add_bookmark().is_some());
+    }
+
+    #[test]
+    fn a_bookmark_in_unchanged_text_stays_put() {
+        let (bookmark, _) = anchored("Light,", TEXT);
+        let original = Document::from_text(TEXT);
+        assert_eq!(original.tokens[bookmark.position].text, "Light,");
+        assert!(!bookmark.lost);
+    }
+
+    #[test]
+    fn a_bookmark_follows_text_inserted_before_it() {
+        let edited = format!("A Tale of Two Cities. Book the First. {}", TEXT);
+        let (bookmark, document) = anchored("Light,", &edited);
+        assert_eq!(document.tokens[bookmark.position].text, "Light,");
+        assert!(!bookmark.lost);
+    }
+
+    #[test]
+    fn a_bookmark_survives_text_changed_slightly() {
+        let edited = TEXT.replace("season of Light,", "season of light —").replace("it was the season", "it was a season");
+        let (bookmark, document) = anchored("Light,", &edited);
+        assert_eq!(document.tokens[bookmark.position].text, "light");
+        assert!(!bookmark.lost);
+    }
+
+    #[test]
+    fn a_bookmark_whose_text_is_gone_is_lost() {
+        let (bookmark, document) = anchored("Light,", "Call me Ishmael. Some years ago, never mind how long precisely.");
+        assert!(bookmark.lost);
+        assert!(bookmark.position <= document.len());
+    }
+}
@@ -1,12 +1,115 @@
-
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ReadingSession {
+    /// When the session started.
     pub date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+    /// The book's fingerprint; empty in sessions recorded before books had one.
+    #[serde(default)]
+    pub book: String,
+    /// Where the book was read from: a file path or URL.
     pub file_path: String,
     pub words_read: usize,
+    /// Seconds spent actually reading, excluding pauses.
     pub reading_time: f64,
     pub avg_speed: u64,
+    pub start_position: usize,
+    pub end_position: usize,
+    pub pauses: usize,
+}
+
+/// Counts one stretch of reading a single book, from opening it until
+/// quitting or switching to another book.
+pub struct SessionTracker {
+    started: DateTime<Utc>,
+    book: String,
+    file_path: String,
+    start_position: usize,
+    pub words_read: usize,
+    pub reading_time: f64,
+    pub pauses: usize,
+}
+
+impl SessionTracker {
+    pub fn start(book: &str, file_path: &str, position: usize) -> SessionTracker {
+        SessionTracker {
+            started: Utc::now(),
+            book: book.to_string(),
+            file_path: file_path.to_string(),
+            start_position: position,
+            words_read: 0,
+            reading_time: 0.0,
+            pauses: 0,
+        }
+    }
+
+    /// Ends the session at `end_position`, or returns `None` when nothing
+    /// was read and there is nothing worth recording.
+    pub fn finish(&self, end_position: usize) -> Option<ReadingSession> {
+        if self.words_read == 0 {
+            return None;
+        }
+        let avg_speed = if self.reading_time > 0.0 {
+            (self.words_read as f64 * 60.0 / self.reading_time).round() as u64
+        } else {
+            0
+        };
+        Some(ReadingSession {
+            date: self.started,
+            end_date: Utc::now(),
+            book: self.book.clone(),
+            file_path: self.file_path.clone(),
+            words_read: self.words_read,
+            reading_time: self.reading_time,
+            avg_speed,
+            start_position: self.start_position,
+            end_position,
+            pauses: self.pauses,
+        })
+    }
+}
+
+/// Running totals kept with each book so its reading volume is available
+/// without scanning the whole history.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ReadingTotals {
+    pub sessions: usize,
+    pub words_read: usize,
+    pub reading_time: f64,
+    pub last_read: Option<DateTime<Utc>>,
+}
+
+impl ReadingTotals {
+    pub fn add(&mut self, session: &ReadingSession) {
+        self.sessions += 1;
+        self.words_read += session.words_read;
+        self.reading_time += session.reading_time;
+        self.last_read = Some(session.end_date);
+    }
+}
+
+/// Reads the history log, skipping lines that cannot be parsed.
+pub fn load_history(path: &Path) -> Vec<ReadingSession> {
+    fs::read_to_string(path)
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends `session` to the history log, one JSON object per line.
+pub fn append_history(path: &Path, session: &ReadingSession) -> io::Result<()> {
+    let mut line = serde_json::to_string(session).map_err(io::Error::other)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
 }
@@ -0,0 +1,879 @@
+//! Global preferences (`config.toml`) plus the reading state of every book
+//! that has been opened (`state.json`).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use rsvp::document::Document;
+use rsvp::extractors::ExtractOptions;
+use rsvp::orp::OrpConfig;
+use rsvp::reader::{Bookmark, DisplayMode, Reader};
+use rsvp::timing::TimingConfig;
+
+use crate::goals::DailyGoal;
+use crate::paths::Paths;
+use crate::reading_session::{append_history, load_history, ReadingSession, ReadingTotals};
+
+/// Version written to `schema_version`; bump it and add a step to
+/// [`migrate`] whenever the layout changes.
+pub const SCHEMA_VERSION: u64 = 1;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub schema_version: u64,
+    pub global: GlobalSettings,
+    /// Keyed by [`Document::fingerprint`], so a book keeps its state when it
+    /// is moved or renamed. Books saved by older versions are keyed by path
+    /// until they are next opened.
+    pub books: BTreeMap<String, BookState>,
+    /// Fields this version does not know about, written back unchanged.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+    /// The settings as last read from or written to disk, used to tell this
+    /// session's changes apart from another session's when saving.
+    #[serde(skip)]
+    base: Option<Box<Settings>>,
+    #[serde(skip)]
+    paths: Option<Paths>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GlobalSettings {
+    pub speed: u64,
+    pub chunk_size: usize,
+    /// Books remembered before the least recently opened are forgotten;
+    /// pinned and bookmarked books are always kept. 0 keeps every book.
+    pub max_saved_books: u64,
+    pub max_bookmarks_per_book: u64,
+    /// Size limit of the extracted-document cache; 0 turns caching off.
+    pub max_cache_mb: u64,
+    pub timing: TimingConfig,
+    pub orp: OrpConfig,
+    pub daily_goal: DailyGoal,
+    pub extract: ExtractOptions,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        GlobalSettings {
+            speed: 300,
+            chunk_size: 1,
+            max_saved_books: 10,
+            max_bookmarks_per_book: 10,
+            max_cache_mb: 256,
+            timing: TimingConfig::default(),
+            orp: OrpConfig::default(),
+            daily_goal: DailyGoal::default(),
+            extract: ExtractOptions::default(),
+            extra: Map::new(),
+        }
+    }
+}
+
+/// Where the reader is in one book and how they like to read it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookState {
+    /// Where the book was last opened from, a file path or URL. Only a hint
+    /// for finding it again; the book is identified by its content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<DateTime<Utc>>,
+    /// Pinned books stay at the top of the recent list and are never forgotten.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Falls back to the global speed when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u64>,
+    /// Falls back to the global chunk size when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<usize>,
+    pub display_mode: SavedDisplayMode,
+    pub last_position: usize,
+    /// Length of the book when it was last opened, for time-to-finish estimates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_words: Option<usize>,
+    pub bookmarks: Vec<Bookmark>,
+    pub totals: ReadingTotals,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl BookState {
+    pub fn speed(&self, global: &GlobalSettings) -> u64 {
+        self.speed.unwrap_or(global.speed)
+    }
+
+    pub fn chunk_size(&self, global: &GlobalSettings) -> usize {
+        self.chunk_size.unwrap_or(global.chunk_size)
+    }
+
+    /// When the book was last opened, or read for books saved before that
+    /// was recorded.
+    pub fn last_used(&self) -> Option<DateTime<Utc>> {
+        self.last_opened.or(self.totals.last_read)
+    }
+
+    /// How far through the book the reader is, from 0 to 1.
+    pub fn progress(&self) -> Option<f64> {
+        self.total_words
+            .filter(|total| *total > 0)
+            .map(|total| (self.last_position as f64 / total as f64).min(1.0))
+    }
+
+    pub fn display_mode(&self, global: &GlobalSettings) -> DisplayMode {
+        match self.display_mode {
+            SavedDisplayMode::Sentence => DisplayMode::Sentence,
+            SavedDisplayMode::WordChunk => DisplayMode::WordChunk(self.chunk_size(global)),
+        }
+    }
+
+    /// Puts `reader` back where this book was left, with its speed, display
+    /// mode and bookmarks.
+    pub fn restore(&self, reader: &mut Reader, global: &GlobalSettings) {
+        reader.set_wpm(self.speed(global));
+        reader.set_mode(DisplayMode::WordChunk(self.chunk_size(global)));
+        reader.set_mode(self.display_mode(global));
+        reader.seek(self.last_position);
+        reader.set_max_bookmarks(global.max_bookmarks_per_book as usize);
+        reader.set_bookmarks(self.bookmarks.clone());
+    }
+
+    /// Records where `reader` is and how it is set up, for [`restore`](Self::restore),
+    /// along with its bookmarks as moved to match the current text.
+    pub fn remember(&mut self, reader: &Reader) {
+        self.last_position = reader.position();
+        self.bookmarks = reader.bookmarks().to_vec();
+        self.speed = Some(reader.wpm());
+        self.set_display_mode(reader.mode());
+    }
+
+    /// Records the display mode, keeping the last chunk size when switching
+    /// to sentences.
+    pub fn set_display_mode(&mut self, display_mode: DisplayMode) {
+        match display_mode {
+            DisplayMode::Sentence => self.display_mode = SavedDisplayMode::Sentence,
+            DisplayMode::WordChunk(size) => {
+                self.display_mode = SavedDisplayMode::WordChunk;
+                self.chunk_size = Some(size);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SavedDisplayMode {
+    #[default]
+    WordChunk,
+    Sentence,
+}
+
+/// Layout of `config.toml`: the global preferences.
+#[derive(Serialize, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    schema_version: u64,
+    #[serde(flatten)]
+    global: GlobalSettings,
+}
+
+/// Layout of `state.json`: everything rsvp remembers about books.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct StateFile {
+    schema_version: u64,
+    books: BTreeMap<String, BookState>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Settings {
+    /// Loads the config and state files, or the defaults when they do not
+    /// exist yet.
+    ///
+    /// `config_file` overrides the standard locations (see
+    /// [`Paths::with_config`]). On first run with the standard locations,
+    /// the legacy `~/.rsvp_settings.json` is migrated into the new files.
+    pub fn load(config_file: Option<&Path>) -> Settings {
+        let paths = match config_file {
+            Some(config_file) => Paths::with_config(config_file),
+            None => match Paths::standard() {
+                Some(paths) => paths,
+                None => return Settings::default(),
+            },
+        };
+        if let Err(err) = paths.create_dirs() {
+            eprintln!("Could not create settings directories: {}", err);
+        }
+
+        let (config, state) = {
+            let _lock = lock(&paths, false);
+            (read_file(&paths.config_file, parse_config), read_file(&paths.state_file(), parse_state))
+        };
+        let config = config.unwrap_or_else(|_| restore(&paths, &paths.config_file, parse_config));
+        let state = state.unwrap_or_else(|_| restore(&paths, &paths.state_file(), parse_state));
+
+        let legacy = (config.is_none() && state.is_none() && config_file.is_none())
+            .then(Paths::legacy_file)
+            .flatten()
+            .and_then(|legacy| fs::read_to_string(legacy).ok())
+            .and_then(|content| Settings::from_json(&content).ok());
+
+        if let Some(mut settings) = legacy {
+            // Written out straight away, so the migration happens only once.
+            settings.paths = Some(paths);
+            if let Err(err) = settings.save() {
+                eprintln!("{}", err);
+            }
+            return settings;
+        }
+
+        let mut settings = Settings::default();
+        if let Some(config) = config {
+            settings.global = config.global;
+        }
+        if let Some(state) = state {
+            settings.books = state.books;
+            settings.extra = state.extra;
+        }
+        settings.schema_version = SCHEMA_VERSION;
+        settings.base = Some(Box::new(settings.snapshot()));
+        settings.paths = Some(paths);
+        settings
+    }
+
+    /// Parses the legacy single-file settings of any schema version.
+    pub fn from_json(content: &str) -> Result<Settings, serde_json::Error> {
+        let value = serde_json::from_str::<Value>(content)?;
+        serde_json::from_value(migrate(value))
+    }
+
+    /// Writes the settings back, first merging in anything another session
+    /// saved since this one read the files.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(paths) = self.paths.clone() else {
+            return Ok(());
+        };
+        self.write(&paths).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Could not save settings to {}: {}", paths.config_file.display(), err),
+            )
+        })
+    }
+
+    /// Saves under an exclusive lock: each file is merged and kept as `.bak`,
+    /// then the new contents are written to a temporary file, synced and
+    /// renamed over it, so a crash never leaves a half-written file.
+    fn write(&mut self, paths: &Paths) -> io::Result<()> {
+        paths.create_dirs()?;
+        let _lock = lock(paths, true)?;
+        let base = self.base.as_deref().map(Settings::snapshot).unwrap_or_default();
+
+        let config_path = &paths.config_file;
+        let on_disk = read_file(config_path, parse_config).ok().flatten();
+        if let Some(on_disk) = &on_disk {
+            fs::copy(config_path, backup_path(config_path))?;
+            if self.global == base.global {
+                self.global = on_disk.global.clone();
+            }
+        }
+
+        let state_path = paths.state_file();
+        let on_disk = read_file(&state_path, parse_state).ok().flatten();
+        if let Some(on_disk) = on_disk {
+            fs::copy(&state_path, backup_path(&state_path))?;
+            if self.extra == base.extra {
+                self.extra = on_disk.extra;
+            }
+            self.merge_books(on_disk.books, &base);
+        }
+
+        self.schema_version = SCHEMA_VERSION;
+        let mut config = ConfigFile {
+            schema_version: SCHEMA_VERSION,
+            global: self.global.clone(),
+        };
+        // TOML has no null, so unknown keys set to null in a legacy file are dropped.
+        strip_nulls(&mut config.global.extra);
+        let state = StateFile {
+            schema_version: SCHEMA_VERSION,
+            books: self.books.clone(),
+            extra: self.extra.clone(),
+        };
+        write_atomic(config_path, &toml::to_string_pretty(&config).map_err(io::Error::other)?)?;
+        write_atomic(&state_path, &serde_json::to_string_pretty(&state).map_err(io::Error::other)?)?;
+
+        self.base = Some(Box::new(self.snapshot()));
+        Ok(())
+    }
+
+    /// Finds the state of `document`, opened from `location`, and returns its
+    /// key in [`books`](Self::books), which is `fingerprint`.
+    ///
+    /// A book not yet known by its fingerprint takes over the state saved
+    /// for whatever was last opened from the same location, which covers
+    /// books saved by older versions and files edited in place. The
+    /// location, title and length are updated either way, and books beyond
+    /// `max_saved_books` are forgotten.
+    pub fn open_book(&mut self, document: &Document, key: String, location: &str) -> String {
+        if !self.books.contains_key(&key) {
+            let previous = self
+                .books
+                .iter()
+                .find(|(old_key, book)| book.path.as_deref().unwrap_or(old_key) == location)
+                .map(|(old_key, _)| old_key.clone());
+            if let Some(book) = previous.and_then(|old_key| self.books.remove(&old_key)) {
+                self.books.insert(key.clone(), book);
+            }
+        }
+        let book = self.books.entry(key.clone()).or_default();
+        book.path = Some(location.to_string());
+        book.title = document.title.clone();
+        book.total_words = Some(document.len());
+        book.last_opened = Some(Utc::now());
+        self.prune_books(&key);
+        key
+    }
+
+    /// Keys of the saved books, pinned ones first, then the most recently
+    /// opened.
+    pub fn recent_books(&self) -> Vec<String> {
+        let mut keys: Vec<&String> = self.books.keys().collect();
+        keys.sort_by_key(|key| {
+            let book = &self.books[*key];
+            (Reverse(book.pinned), Reverse(book.last_used()))
+        });
+        keys.into_iter().cloned().collect()
+    }
+
+    /// Forgets the least recently opened books beyond `max_saved_books`,
+    /// except pinned and bookmarked ones and the book `keep`.
+    fn prune_books(&mut self, keep: &str) {
+        let limit = self.global.max_saved_books as usize;
+        if limit == 0 || self.books.len() <= limit {
+            return;
+        }
+        let excess = self.books.len() - limit;
+        let evictable: Vec<String> = self
+            .recent_books()
+            .into_iter()
+            .filter(|key| {
+                let book = &self.books[key];
+                key != keep && !book.pinned && book.bookmarks.is_empty()
+            })
+            .collect();
+        for key in evictable.iter().rev().take(excess) {
+            self.books.remove(key);
+        }
+    }
+
+    /// Adds a finished reading session to its book's totals and to the
+    /// history log, then saves. The totals are saved even when the log
+    /// cannot be written.
+    pub fn record_session(&mut self, session: ReadingSession) -> io::Result<()> {
+        let logged = match &self.paths {
+            Some(paths) => append_history(&paths.history_file(), &session).map_err(|err| {
+                io::Error::new(err.kind(), format!("Could not record reading session: {}", err))
+            }),
+            None => Ok(()),
+        };
+        let key = if session.book.is_empty() { &session.file_path } else { &session.book };
+        self.books.entry(key.clone()).or_default().totals.add(&session);
+        let saved = self.save();
+        logged.and(saved)
+    }
+
+    /// Where data that can be rebuilt, such as extracted documents, is kept.
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.paths.as_ref().map(|paths| paths.cache_dir.as_path())
+    }
+
+    /// Every recorded reading session, oldest first.
+    pub fn history(&self) -> Vec<ReadingSession> {
+        self.paths
+            .as_ref()
+            .map(|paths| load_history(&paths.history_file()))
+            .unwrap_or_default()
+    }
+
+    /// Folds in books saved by another session since this one last read or
+    /// wrote the state file. Whatever this session changed wins; bookmarks
+    /// added on either side are kept, and those deleted on either side stay
+    /// deleted.
+    fn merge_books(&mut self, on_disk: BTreeMap<String, BookState>, base: &Settings) {
+        for (key, disk_book) in on_disk {
+            let base_book = base.books.get(&key);
+            match self.books.get_mut(&key) {
+                // Untouched here, so take the other session's state.
+                Some(book) if Some(&*book) == base_book => *book = disk_book,
+                Some(book) => {
+                    book.bookmarks.retain(|bookmark| {
+                        let deleted_elsewhere = base_book.is_some_and(|base| base.bookmarks.contains(bookmark))
+                            && !disk_book.bookmarks.contains(bookmark);
+                        !deleted_elsewhere
+                    });
+                    for bookmark in disk_book.bookmarks {
+                        let added_elsewhere = !base_book.is_some_and(|base| base.bookmarks.contains(&bookmark));
+                        if added_elsewhere && !book.bookmarks.contains(&bookmark) {
+                            book.bookmarks.push(bookmark);
+                        }
+                    }
+                }
+                // New in the other session; a book missing here but present
+                // in `base` was removed by this session and stays removed.
+                None if base_book.is_none() => {
+                    self.books.insert(key, disk_book);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// A copy of the settings without the merge base.
+    fn snapshot(&self) -> Settings {
+        Settings {
+            schema_version: self.schema_version,
+            global: self.global.clone(),
+            books: self.books.clone(),
+            extra: self.extra.clone(),
+            base: None,
+            paths: None,
+        }
+    }
+}
+
+fn parse_config(content: &str) -> Result<ConfigFile, String> {
+    toml::from_str(content).map_err(|err| err.to_string())
+}
+
+fn parse_state(content: &str) -> Result<StateFile, String> {
+    serde_json::from_str(content).map_err(|err| err.to_string())
+}
+
+/// Reads and parses a settings file, returning `None` when it does not exist.
+fn read_file<T>(path: &Path, parse: fn(&str) -> Result<T, String>) -> Result<Option<T>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => parse(&content).map(Some),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Recovers a settings file that could not be parsed, falling back to the
+/// `.bak` copy from the previous save. The broken file is renamed to
+/// `.invalid` so that the next save does not destroy it.
+///
+/// This takes the exclusive lock and reads the file again first, since
+/// another session may have replaced it since it was read.
+fn restore<T>(paths: &Paths, path: &Path, parse: fn(&str) -> Result<T, String>) -> Option<T> {
+    let _lock = lock(paths, true);
+    let err = match read_file(path, parse) {
+        Ok(contents) => return contents,
+        Err(err) => err,
+    };
+    let invalid = with_suffix(path, ".invalid");
+    let backup = read_file(&backup_path(path), parse).ok().flatten();
+    eprintln!(
+        "Could not read settings from {}: {}. Moved it to {}{}.",
+        path.display(),
+        err,
+        invalid.display(),
+        if backup.is_some() { " and restored the backup" } else { "" }
+    );
+    let _ = fs::rename(path, invalid);
+    backup
+}
+
+/// Replaces `path` with `contents` by writing and syncing a temporary file
+/// and renaming it into place.
+fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let temp_path = with_suffix(path, ".tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    // Sync the directory too so the rename itself survives a crash.
+    if let Some(dir) = path.parent().and_then(|dir| File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Takes an advisory lock in the state directory, shared for reading and
+/// exclusive for writing, held until the returned file is dropped.
+fn lock(paths: &Paths, exclusive: bool) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(paths.state_dir.join("lock"))?;
+    if exclusive {
+        file.lock()?;
+    } else {
+        file.lock_shared()?;
+    }
+    Ok(file)
+}
+
+/// Upgrades a settings file written by an older version to the current
+/// layout, one schema version at a time.
+fn migrate(mut value: Value) -> Value {
+    if !value.is_object() {
+        value = json!({});
+    }
+    let version = value.get("schema_version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version < 1 {
+        migrate_untyped(&mut value);
+    }
+
+    value["schema_version"] = json!(SCHEMA_VERSION);
+    value
+}
+
+/// Version 0 is the hand-written JSON from before settings were typed: values
+/// may have the wrong type, and a bad value must not lose the whole file.
+fn migrate_untyped(value: &mut Value) {
+    let root = value.as_object_mut().unwrap();
+
+    let mut global = match root.remove("global") {
+        Some(Value::Object(global)) => global,
+        _ => Map::new(),
+    };
+    for key in ["speed", "chunk_size", "max_saved_books", "max_bookmarks_per_book"] {
+        coerce_number(&mut global, key);
+    }
+    for (key, keep) in [("timing", Value::is_number as fn(&Value) -> bool), ("orp", Value::is_boolean)] {
+        match global.get_mut(key) {
+            Some(Value::Object(fields)) => fields.retain(|_, field| keep(field)),
+            Some(_) => {
+                global.remove(key);
+            }
+            None => {}
+        }
+    }
+    root.insert("global".to_string(), Value::Object(global));
+
+    let mut books = match root.remove("books") {
+        Some(Value::Object(books)) => books,
+        _ => Map::new(),
+    };
+    books.retain(|_, book| book.is_object());
+    for book in books.values_mut() {
+        let book = book.as_object_mut().unwrap();
+        for key in ["speed", "chunk_size", "last_position"] {
+            coerce_number(book, key);
+        }
+        if !matches!(book.get("display_mode").and_then(Value::as_str), Some("word_chunk" | "sentence")) {
+            book.remove("display_mode");
+        }
+        let bookmarks = match book.remove("bookmarks") {
+            Some(Value::Array(bookmarks)) => bookmarks,
+            _ => Vec::new(),
+        };
+        let bookmarks: Vec<Value> = bookmarks
+            .into_iter()
+            .filter_map(|bookmark| match bookmark {
+                Value::Number(position) => Some(json!({ "position": position })),
+                Value::Object(mut bookmark) => {
+                    coerce_number(&mut bookmark, "position");
+                    if bookmark.get("preview").is_some_and(|preview| !preview.is_string()) {
+                        bookmark.remove("preview");
+                    }
+                    bookmark.contains_key("position").then_some(Value::Object(bookmark))
+                }
+                _ => None,
+            })
+            .collect();
+        book.insert("bookmarks".to_string(), Value::Array(bookmarks));
+    }
+    root.insert("books".to_string(), Value::Object(books));
+}
+
+/// Removes nulls from `map`, including inside its objects and arrays.
+fn strip_nulls(map: &mut Map<String, Value>) {
+    map.retain(|_, value| !value.is_null());
+    for value in map.values_mut() {
+        strip_null_values(value);
+    }
+}
+
+fn strip_null_values(value: &mut Value) {
+    match value {
+        Value::Object(map) => strip_nulls(map),
+        Value::Array(values) => {
+            values.retain(|value| !value.is_null());
+            values.iter_mut().for_each(strip_null_values);
+        }
+        _ => {}
+    }
+}
+
+/// Turns a numeric string into a number and drops values that are neither.
+fn coerce_number(map: &mut Map<String, Value>, key: &str) {
+    let number = match map.get(key) {
+        None | Some(Value::Null) => None,
+        Some(value) if value.is_u64() => return,
+        Some(Value::String(text)) => text.trim().parse::<u64>().ok(),
+        Some(Value::Number(number)) => number.as_f64().filter(|n| *n >= 0.0).map(|n| n as u64),
+        Some(_) => None,
+    };
+    match number {
+        Some(number) => {
+            map.insert(key.to_string(), json!(number));
+        }
+        None => {
+            map.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own under the system temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsvp-settings-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn positions(book: &BookState) -> Vec<usize> {
+        book.bookmarks.iter().map(|bookmark| bookmark.position).collect()
+    }
+
+    #[test]
+    fn migrates_legacy_settings_file() {
+        let legacy = r#"{
+            "global": {"speed": 450, "chunk_size": 2},
+            "books": {
+                "/books/moby.txt": {
+                    "speed": 500,
+                    "chunk_size": 3,
+                    "last_position": 1200,
+                    "display_mode": "sentence",
+                    "bookmarks": [40, {"position": 80, "preview": "Call me Ishmael"}]
+                }
+            }
+        }"#;
+        let settings = Settings::from_json(legacy).unwrap();
+
+        assert_eq!(settings.schema_version, SCHEMA_VERSION);
+        assert_eq!(settings.global.speed, 450);
+        assert_eq!(settings.global.chunk_size, 2);
+        let book = &settings.books["/books/moby.txt"];
+        assert_eq!(book.speed, Some(500));
+        assert_eq!(book.chunk_size, Some(3));
+        assert_eq!(book.last_position, 1200);
+        assert_eq!(book.display_mode, SavedDisplayMode::Sentence);
+        assert_eq!(positions(book), [40, 80]);
+        assert_eq!(book.bookmarks[1].preview, "Call me Ishmael");
+    }
+
+    #[test]
+    fn saves_a_legacy_file_with_null_fields() {
+        let dir = temp_dir("nulls");
+        let config = dir.join("config.toml");
+        let legacy = r#"{
+            "global": {"speed": null, "theme": null, "window": {"width": 80, "height": null}, "fonts": ["mono", null]},
+            "books": {"a.txt": {"last_position": 5, "note": null}}
+        }"#;
+        let mut settings = Settings::from_json(legacy).unwrap();
+        settings.paths = Some(Paths::with_config(&config));
+        settings.save().unwrap();
+        // And again, now that the files exist.
+        settings.save().unwrap();
+
+        let saved = Settings::load(Some(&config));
+        assert_eq!(saved.global.speed, GlobalSettings::default().speed);
+        assert_eq!(saved.global.extra.get("theme"), None);
+        assert_eq!(saved.global.extra["window"], json!({"width": 80}));
+        assert_eq!(saved.global.extra["fonts"], json!(["mono"]));
+        assert_eq!(saved.books["a.txt"].last_position, 5);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn migrates_numbers_stored_as_strings() {
+        let legacy = r#"{
+            "global": {"speed": "350", "chunk_size": " 4 ", "max_saved_books": "lots"},
+            "books": {
+                "a.txt": {
+                    "speed": "600",
+                    "last_position": "25",
+                    "display_mode": "sideways",
+                    "bookmarks": [{"position": "7"}, {"position": "x"}, "twelve"]
+                }
+            }
+        }"#;
+        let settings = Settings::from_json(legacy).unwrap();
+
+        assert_eq!(settings.global.speed, 350);
+        assert_eq!(settings.global.chunk_size, 4);
+        assert_eq!(settings.global.max_saved_books, GlobalSettings::default().max_saved_books);
+        let book = &settings.books["a.txt"];
+        assert_eq!(book.speed, Some(600));
+        assert_eq!(book.last_position, 25);
+        assert_eq!(book.display_mode, SavedDisplayMode::WordChunk);
+        assert_eq!(positions(book), [7]);
+    }
+
+    #[test]
+    fn coerce_number_keeps_only_whole_non_negative_numbers() {
+        let mut map = json!({"a": "12", "b": -3, "c": 2.7, "d": true, "e": null, "f": 5, "g": "1.5"})
+            .as_object()
+            .unwrap()
+            .clone();
+        for key in ["a", "b", "c", "d", "e", "f", "g", "missing"] {
+            coerce_number(&mut map, key);
+        }
+        assert_eq!(Value::Object(map), json!({"a": 12, "c": 2, "f": 5}));
+    }
+
+    #[test]
+    fn garbage_legacy_settings_give_defaults() {
+        assert!(Settings::from_json("not json").is_err());
+        for garbage in ["[1, 2]", r#"{"global": 5, "books": []}"#, r#"{"books": {"a.txt": "b"}}"#] {
+            let settings = Settings::from_json(garbage).unwrap();
+            assert_eq!(settings.global, GlobalSettings::default());
+            assert!(settings.books.is_empty());
+        }
+    }
+
+    #[test]
+    fn missing_files_load_defaults() {
+        let dir = temp_dir("missing");
+        let settings = Settings::load(Some(&dir.join("config.toml")));
+
+        assert_eq!(settings.global, GlobalSettings::default());
+        assert!(settings.books.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn garbage_files_are_moved_aside_and_backups_restored() {
+        let dir = temp_dir("garbage");
+        let config = dir.join("config.toml");
+        let state = dir.join("state.json");
+        fs::write(&config, "speed = [[[").unwrap();
+        fs::write(backup_path(&config), "speed = 420\n").unwrap();
+        fs::write(&state, "{ not json").unwrap();
+
+        let settings = Settings::load(Some(&config));
+
+        assert_eq!(settings.global.speed, 420);
+        assert!(settings.books.is_empty());
+        assert_eq!(fs::read_to_string(with_suffix(&config, ".invalid")).unwrap(), "speed = [[[");
+        assert_eq!(fs::read_to_string(with_suffix(&state, ".invalid")).unwrap(), "{ not json");
+        assert!(!state.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn add_bookmark(settings: &mut Settings, position: usize) {
+        let bookmark = Bookmark { position, ..Bookmark::default() };
+        settings.books.entry("book".to_string()).or_default().bookmarks.push(bookmark);
+    }
+
+    #[test]
+    fn saves_keep_bookmarks_added_by_two_sessions() {
+        let dir = temp_dir("merge-add");
+        let config = dir.join("config.toml");
+        let mut first = Settings::load(Some(&config));
+        add_bookmark(&mut first, 10);
+        first.save().unwrap();
+
+        let mut first = Settings::load(Some(&config));
+        let mut second = Settings::load(Some(&config));
+        add_bookmark(&mut first, 20);
+        first.save().unwrap();
+        add_bookmark(&mut second, 30);
+        second.save().unwrap();
+
+        let saved = Settings::load(Some(&config));
+        assert_eq!(positions(&saved.books["book"]), [10, 30, 20]);
+        assert_eq!(positions(&second.books["book"]), [10, 30, 20]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saves_do_not_bring_back_a_bookmark_deleted_elsewhere() {
+        let dir = temp_dir("merge-delete");
+        let config = dir.join("config.toml");
+        let mut first = Settings::load(Some(&config));
+        add_bookmark(&mut first, 10);
+        add_bookmark(&mut first, 20);
+        first.save().unwrap();
+
+        let mut first = Settings::load(Some(&config));
+        let mut second = Settings::load(Some(&config));
+        first.books.get_mut("book").unwrap().bookmarks.remove(0);
+        first.save().unwrap();
+        add_bookmark(&mut second, 30);
+        second.save().unwrap();
+
+        let saved = Settings::load(Some(&config));
+        assert_eq!(positions(&saved.books["book"]), [20, 30]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Opens `text` as if read from `location`, as the loader would.
+    fn open(settings: &mut Settings, text: &str, location: &str) -> String {
+        let document = Document::from_text(text);
+        let fingerprint = document.fingerprint();
+        settings.open_book(&document, fingerprint, location)
+    }
+
+    #[test]
+    fn a_resaved_and_moved_book_keeps_its_state() {
+        let mut settings = Settings::default();
+        let key = open(&mut settings, "Call me Ishmael. Some years ago...", "/books/moby.txt");
+        settings.books.get_mut(&key).unwrap().last_position = 2;
+
+        // Re-saved by another editor with different quotes and line breaks.
+        let moved = open(&mut settings, "Call me Ishmael.\r\n\r\nSome years ago…", "/elsewhere/Moby Dick.txt");
+        assert_eq!(moved, key);
+        assert_eq!(settings.books.len(), 1);
+        assert_eq!(settings.books[&key].last_position, 2);
+        assert_eq!(settings.books[&key].path.as_deref(), Some("/elsewhere/Moby Dick.txt"));
+    }
+
+    #[test]
+    fn a_book_edited_in_place_is_keyed_again() {
+        let mut settings = Settings::default();
+        // As saved by a version that keyed books by path.
+        settings.books.insert("/books/moby.txt".to_string(), BookState { last_position: 2, ..BookState::default() });
+
+        let first = open(&mut settings, "Call me Ishmael.", "/books/moby.txt");
+        let second = open(&mut settings, "Call me Ishmael, please.", "/books/moby.txt");
+        assert_ne!(first, second);
+        assert_eq!(settings.books.keys().collect::<Vec<_>>(), [&second]);
+        assert_eq!(settings.books[&second].last_position, 2);
+    }
+}
@@ -0,0 +1,264 @@
+//! Reading statistics computed from the session history, for the dashboard.
+
+use chrono::{Datelike, Days, Local, NaiveDate};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::goals::{streak, DailyGoal};
+use crate::reading_session::ReadingSession;
+use crate::settings::{BookState, Settings};
+
+/// How many days and weeks the bar charts cover.
+const DAYS_SHOWN: u64 = 14;
+const WEEKS_SHOWN: u64 = 8;
+
+pub struct ReadingStats {
+    /// Words read on each of the last few days, oldest first.
+    pub daily_words: Vec<(NaiveDate, usize)>,
+    /// Words read in each of the last few weeks (by their Monday), oldest first.
+    pub weekly_words: Vec<(NaiveDate, usize)>,
+    /// Average effective WPM on each of the last few days, 0 where nothing was read.
+    pub daily_wpm: Vec<(NaiveDate, u64)>,
+    /// Days in a row, up to today or yesterday, meeting the daily goal.
+    pub streak: usize,
+    pub total_words: usize,
+    /// Reading time per book in seconds, longest first.
+    pub time_per_book: Vec<(String, f64)>,
+    /// Estimated seconds left in each book, in the order of the recent books
+    /// list, when it can be worked out.
+    pub time_to_finish: Vec<(String, Option<f64>)>,
+}
+
+impl ReadingStats {
+    pub fn new(history: &[ReadingSession], settings: &Settings, goal: &DailyGoal) -> ReadingStats {
+        let books = &settings.books;
+        let today = Local::now().date_naive();
+
+        let mut words_by_day: HashMap<NaiveDate, usize> = HashMap::new();
+        let mut time_by_day: HashMap<NaiveDate, f64> = HashMap::new();
+        let mut time_by_book: HashMap<&str, f64> = HashMap::new();
+        for session in history {
+            let day = session.date.with_timezone(&Local).date_naive();
+            *words_by_day.entry(day).or_default() += session.words_read;
+            *time_by_day.entry(day).or_default() += session.reading_time;
+            let book = if session.book.is_empty() { &session.file_path } else { &session.book };
+            *time_by_book.entry(book).or_default() += session.reading_time;
+        }
+
+        let days: Vec<NaiveDate> = (0..DAYS_SHOWN)
+            .rev()
+            .filter_map(|ago| today.checked_sub_days(Days::new(ago)))
+            .collect();
+        let daily_words = days
+            .iter()
+            .map(|day| (*day, words_by_day.get(day).copied().unwrap_or(0)))
+            .collect();
+        let daily_wpm = days
+            .iter()
+            .map(|day| {
+                let words = words_by_day.get(day).copied().unwrap_or(0);
+                let seconds = time_by_day.get(day).copied().unwrap_or(0.0);
+                (*day, wpm(words, seconds).unwrap_or(0))
+            })
+            .collect();
+
+        let this_week = week_start(today);
+        let weekly_words = (0..WEEKS_SHOWN)
+            .rev()
+            .filter_map(|ago| this_week.checked_sub_days(Days::new(ago * 7)))
+            .map(|week| {
+                let words = words_by_day
+                    .iter()
+                    .filter(|(day, _)| week_start(**day) == week)
+                    .map(|(_, words)| words)
+                    .sum();
+                (week, words)
+            })
+            .collect();
+
+        let mut time_per_book: Vec<(String, f64)> = time_by_book
+            .into_iter()
+            .map(|(key, seconds)| (book_name(key, books.get(key)), seconds))
+            .collect();
+        time_per_book.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // Books are estimated at their own pace, falling back to the overall one.
+        let overall_wpm = wpm(
+            history.iter().map(|session| session.words_read).sum(),
+            history.iter().map(|session| session.reading_time).sum(),
+        );
+        let time_to_finish = settings
+            .recent_books()
+            .into_iter()
+            .map(|key| {
+                let book = &books[&key];
+                let remaining = book
+                    .total_words
+                    .map(|total| total.saturating_sub(book.last_position));
+                let pace = wpm(book.totals.words_read, book.totals.reading_time).or(overall_wpm);
+                let seconds = remaining
+                    .zip(pace)
+                    .map(|(words, pace)| words as f64 * 60.0 / pace as f64);
+                (book_name(&key, Some(book)), seconds)
+            })
+            .collect();
+
+        ReadingStats {
+            daily_words,
+            weekly_words,
+            daily_wpm,
+            streak: streak(history, goal),
+            total_words: words_by_day.values().sum(),
+            time_per_book,
+            time_to_finish,
+        }
+    }
+}
+
+/// Words per minute, or `None` when no time was spent or it rounds to 0.
+fn wpm(words: usize, seconds: f64) -> Option<u64> {
+    (seconds > 0.0 && words > 0)
+        .then(|| (words as f64 * 60.0 / seconds).round() as u64)
+        .filter(|&wpm| wpm > 0)
+}
+
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Days::new(day.weekday().num_days_from_monday() as u64)
+}
+
+/// A short name for a book: its title, else the file name it was last
+/// opened from, or the whole URL for web pages.
+fn book_name(key: &str, book: Option<&BookState>) -> String {
+    if let Some(title) = book.and_then(|book| book.title.clone()) {
+        return title;
+    }
+    let path = book.and_then(|book| book.path.as_deref()).unwrap_or(key);
+    if path.contains("://") {
+        return path.to_string();
+    }
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Formats seconds as "1h 05m", "12m" or "40s".
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m", seconds / 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    use crate::reading_session::ReadingTotals;
+
+    /// A session on `day` at noon, local time.
+    fn session(day: NaiveDate, book: &str, words_read: usize, reading_time: f64) -> ReadingSession {
+        let date = day.and_hms_opt(12, 0, 0).unwrap().and_local_timezone(Local).unwrap().with_timezone(&Utc);
+        ReadingSession {
+            date,
+            end_date: date,
+            book: book.to_string(),
+            file_path: format!("/books/{}.txt", book),
+            words_read,
+            reading_time,
+            avg_speed: 0,
+            start_position: 0,
+            end_position: words_read,
+            pauses: 0,
+        }
+    }
+
+    fn book(title: &str, total_words: usize, last_position: usize, words_read: usize, reading_time: f64) -> BookState {
+        BookState {
+            title: Some(title.to_string()),
+            total_words: Some(total_words),
+            last_position,
+            totals: ReadingTotals { words_read, reading_time, ..ReadingTotals::default() },
+            ..BookState::default()
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(week_start(monday), monday);
+        assert_eq!(week_start(NaiveDate::from_ymd_opt(2024, 1, 7).unwrap()), monday);
+        assert_eq!(week_start(NaiveDate::from_ymd_opt(2024, 1, 8).unwrap()), monday + Days::new(7));
+    }
+
+    #[test]
+    fn buckets_words_by_calendar_week() {
+        let this_week = week_start(Local::now().date_naive());
+        let history = [
+            session(this_week, "a", 100, 60.0),
+            // The Sunday before belongs to last week, with its Monday.
+            session(this_week - Days::new(1), "a", 20, 60.0),
+            session(this_week - Days::new(7), "a", 3, 60.0),
+            session(this_week - Days::new(8), "a", 4000, 60.0),
+        ];
+        let stats = ReadingStats::new(&history, &Settings::default(), &DailyGoal::Off);
+        let weeks: Vec<usize> = stats.weekly_words.iter().rev().take(3).map(|(_, words)| *words).collect();
+        assert_eq!(weeks, [100, 23, 4000]);
+        assert_eq!(stats.weekly_words.last().unwrap().0, this_week);
+        assert_eq!(stats.total_words, 4123);
+    }
+
+    #[test]
+    fn totals_reading_time_per_book() {
+        let today = Local::now().date_naive();
+        let mut settings = Settings::default();
+        settings.books.insert("a".to_string(), book("Moby-Dick", 1000, 0, 0, 0.0));
+        let mut legacy = session(today, "", 10, 30.0);
+        legacy.file_path = "/books/old.txt".to_string();
+        let history = [
+            session(today, "a", 10, 60.0),
+            session(today - Days::new(3), "a", 10, 90.0),
+            session(today, "b", 10, 100.0),
+            legacy,
+        ];
+        let stats = ReadingStats::new(&history, &settings, &DailyGoal::Off);
+        assert_eq!(
+            stats.time_per_book,
+            [("Moby-Dick".to_string(), 150.0), ("b".to_string(), 100.0), ("old.txt".to_string(), 30.0)]
+        );
+    }
+
+    #[test]
+    fn estimates_time_to_finish_at_each_books_pace() {
+        let mut settings = Settings::default();
+        // 300 words a minute, 600 words to go.
+        settings.books.insert("a".to_string(), book("Paced", 1000, 400, 600, 120.0));
+        settings.books.insert("b".to_string(), book("Finished", 1000, 1000, 600, 120.0));
+        // Never read, so it goes at the overall pace of 300 words a minute.
+        settings.books.insert("c".to_string(), book("Unread", 300, 0, 0, 0.0));
+        let history = [session(Local::now().date_naive(), "a", 600, 120.0)];
+        let stats = ReadingStats::new(&history, &settings, &DailyGoal::Off);
+
+        let mut estimates = stats.time_to_finish.clone();
+        estimates.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            estimates,
+            [("Finished".to_string(), Some(0.0)), ("Paced".to_string(), Some(120.0)), ("Unread".to_string(), Some(60.0))]
+        );
+    }
+
+    #[test]
+    fn cannot_estimate_time_to_finish_without_a_pace() {
+        let mut settings = Settings::default();
+        settings.books.insert("a".to_string(), book("Unread", 1000, 0, 0, 0.0));
+        // One word in an hour rounds to 0 words a minute.
+        settings.books.insert("b".to_string(), book("Slow", 1000, 1, 1, 3600.0));
+        let stats = ReadingStats::new(&[], &settings, &DailyGoal::Off);
+        assert!(stats.time_to_finish.iter().all(|(_, seconds)| seconds.is_none()), "{:?}", stats.time_to_finish);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::document::{Document, Token};
+
+/// Short words the eye takes in at a glance.
+const FUNCTION_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "he", "if", "in", "is", "it",
+    "of", "on", "or", "she", "so", "the", "to", "was", "we", "you",
+];
+
+/// Multipliers applied to a word's base duration (`60000 / wpm` ms).
+///
+/// Stored under `global.timing` in the settings file; setting every
+/// multiplier to 1.0 gives the old fixed-delay behaviour.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimingConfig {
+    /// Words ending in `,` `;` or `:`.
+    pub comma: f64,
+    /// Words that end a sentence.
+    pub sentence_end: f64,
+    /// Words that end a paragraph (used instead of `sentence_end`).
+    pub paragraph_end: f64,
+    /// Words longer than this many characters get extra time.
+    pub long_word_length: usize,
+    /// Extra time per character beyond `long_word_length`.
+    pub long_word_extra: f64,
+    /// Words containing digits.
+    pub number: f64,
+    /// Short function words such as "the" or "of".
+    pub function_word: f64,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        TimingConfig {
+            comma: 1.5,
+            sentence_end: 2.0,
+            paragraph_end: 2.5,
+            long_word_length: 8,
+            long_word_extra: 0.1,
+            number: 1.5,
+            function_word: 0.7,
+        }
+    }
+}
+
+impl TimingConfig {
+    /// Relative display time of a single token before normalisation.
+    pub fn weight(&self, token: &Token) -> f64 {
+        let text = token.text.trim_end_matches(['"', '\'', ')', ']', '”', '’', '»']);
+
+        let pause = if token.flags.paragraph_end {
+            self.paragraph_end
+        } else if token.flags.sentence_end {
+            self.sentence_end
+        } else if text.ends_with([',', ';', ':']) {
+            self.comma
+        } else {
+            1.0
+        };
+
+        let letters = text.chars().filter(|c| c.is_alphanumeric()).count();
+        let shape = if text.chars().any(|c| c.is_ascii_digit()) {
+            self.number
+        } else if letters > self.long_word_length {
+            1.0 + (letters - self.long_word_length) as f64 * self.long_word_extra
+        } else if FUNCTION_WORDS.contains(&text.to_lowercase().as_str()) {
+            self.function_word
+        } else {
+            1.0
+        };
+
+        (pause * shape).max(0.1)
+    }
+}
+
+/// Per-unit display durations for one document.
+///
+/// Weights are divided by the document's mean weight, so a full read still
+/// averages out at the chosen words per minute.
+pub struct Timing {
+    config: TimingConfig,
+    scale: f64,
+}
+
+impl Timing {
+    pub fn new(config: TimingConfig, document: &Document) -> Timing {
+        let total: f64 = document.tokens.iter().map(|token| config.weight(token)).sum();
+        let scale = if total > 0.0 {
+            document.len() as f64 / total
+        } else {
+            1.0
+        };
+        Timing { config, scale }
+    }
+
+    pub fn config(&self) -> &TimingConfig {
+        &self.config
+    }
+
+    /// How long to show `tokens` at `wpm` words per minute. A chunk lasts
+    /// as long as its words would one at a time, so a chunk of three words
+    /// stays up about three times as long as one word.
+    pub fn duration(&self, tokens: &[Token], wpm: u64) -> Duration {
+        let base_ms = 60000.0 / wpm.max(1) as f64;
+        let weight: f64 = tokens.iter().map(|token| self.config.weight(token)).sum();
+        // Empty units (end of text) still tick at the base rate.
+        let weight = if tokens.is_empty() { 1.0 } else { weight * self.scale };
+        Duration::from_secs_f64(base_ms * weight / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "The quick brown fox, which nobody had seen since 1999, jumped over \
+        the extraordinarily lazy dog. Then it ran away!\n\nA new paragraph starts here; it is short.";
+
+    /// Duration of each token on its own, in units of the base duration.
+    fn weights(timing: &Timing, document: &Document) -> Vec<f64> {
+        document
+            .tokens
+            .iter()
+            .map(|token| timing.duration(std::slice::from_ref(token), 60_000).as_secs_f64() * 1000.0)
+            .collect()
+    }
+
+    #[test]
+    fn mean_normalised_weight_is_one() {
+        let document = Document::from_text(TEXT);
+        let timing = Timing::new(TimingConfig::default(), &document);
+        let weights = weights(&timing, &document);
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+        assert!((mean - 1.0).abs() < 1e-6, "mean weight {}", mean);
+        // The weights themselves vary.
+        assert!(weights.iter().any(|&weight| weight > 1.5));
+        assert!(weights.iter().any(|&weight| weight < 1.0));
+    }
+
+    #[test]
+    fn a_chunk_lasts_as_long_as_its_words() {
+        let document = Document::from_text(TEXT);
+        let timing = Timing::new(TimingConfig::default(), &document);
+        let words: Duration = (0..3).map(|i| timing.duration(&document.tokens[i..i + 1], 300)).sum();
+        let chunk = timing.duration(&document.tokens[0..3], 300);
+        assert!((chunk.as_secs_f64() - words.as_secs_f64()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_units_take_the_base_duration() {
+        let document = Document::from_text(TEXT);
+        let timing = Timing::new(TimingConfig::default(), &document);
+        assert_eq!(timing.duration(&[], 300), Duration::from_millis(200));
+    }
+}
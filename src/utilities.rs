@@ -1,28 +1,28 @@
 #![allow(unused_mut)]
-use unicode_segmentation::UnicodeSegmentation;
 use std::fs;
-use std::io::{Read, stdout};
-use dirs_next::home_dir;
+use std::io::stdout;
 use crossterm::event::{self, KeyCode, KeyEvent};
 use crossterm::terminal;
 use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
 
 use ratatui::style::{Style, Color};
 use ratatui::Terminal;
-use epub::doc::EpubDoc;
-use pdf_extract::extract_text;
-use scraper::{Html, Selector};
-use pulldown_cmark::{Parser, Options, Event};
-use zip::read::ZipArchive;
-use xml::reader::{EventReader, XmlEvent};
-use std::collections::HashMap;
-use serde_json::{json, Value};
+use rsvp::document::Document;
+use rsvp::extractors::docx::extract_docx;
+use rsvp::extractors::epub::extract_epub;
+use rsvp::extractors::markdown::markdown_to_document;
+use rsvp::extractors::markup::html_to_document;
+use rsvp::extractors::odt::extract_odt;
+use rsvp::extractors::pdf::extract_pdf;
+use rsvp::extractors::text::read_text;
+use rsvp::extractors::{ExtractError, ExtractOptions, Progress};
+use crate::settings::{BookState, Settings};
+use std::path::Path;
 //use std::io::{Write, Read};
-use std::fs::{File, read_dir};
-use std::io::Write ;
+use std::fs::read_dir;
 
 /// List of supported file types
-const SUPPORTED_FILE_TYPES: &[&str] = &["pdf",  "docx", "odt", "txt", "html", "htm", "md"]; // Removed "epub" because it was crashing
+const SUPPORTED_FILE_TYPES: &[&str] = &["pdf", "epub", "docx", "odt", "txt", "html", "htm", "md"];
 
 pub fn browse_files_ui() -> Option<String> {
     let mut current_dir = std::env::current_dir().expect("Failed to get current directory");
@@ -162,128 +162,59 @@ fn get_file_entries(dir: &std::path::Path) -> Vec<String> {
 
 
 
-/* supports: PDF, docx, txt, html, MD */
-pub fn get_content(file_path: &str) -> String {
+/* supports: PDF, EPUB, docx, odt, txt, html, MD */
+/// Loads a file, reporting progress to `progress` for the formats that are
+/// slow to extract (PDF, EPUB).
+pub fn get_content(file_path: &str, options: &ExtractOptions, progress: &Progress) -> Result<Document, ExtractError> {
     if file_path.ends_with(".pdf") {
-        // Extract text from PDF
-        match extract_text(file_path) {
-            Ok(text) => text,
-            Err(e) => {
-                eprintln!("Failed to extract text from PDF '{}': {}", file_path, e);
-                String::new()
-            },
-        }
+        // Extract text from PDF, page by page with outline chapters
+        extract_pdf(file_path, progress)
     } else if file_path.ends_with(".epub") {
-        // Extract text from EPUB
-        match EpubDoc::new(file_path) {
-            Ok(mut doc) => {
-                let mut text = String::new();
-                while let Ok(page) = doc.get_current_str() {
-                    text.push_str(&page);
-                    let _ = doc.go_next();
-                }
-                text
-            }
-            Err(_) => panic!("Failed to extract text from EPUB"),
-        }
+        // Extract text from EPUB, chapter by chapter in spine order
+        extract_epub(file_path, progress)
     } else if file_path.ends_with(".docx") {
-        // Extract text from DOCX
-        let file = File::open(file_path).expect("Failed to open DOCX file");
-        let mut archive = ZipArchive::new(file).expect("Failed to read DOCX ZIP structure");
-
-        let mut text = String::new();
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).expect("Failed to read DOCX entry");
-            let file_name = file.name().to_string();
-
-            if file_name == "word/document.xml" {
-                let mut content = String::new();
-                file.read_to_string(&mut content).expect("Failed to extract XML");
-
-                // Strip XML tags (a basic approach)
-                text = content
-                    .replace("<w:t>", "")
-                    .replace("</w:t>", " ")
-                    .replace("<w:p>", "\n")
-                    .replace("</w:p>", "\n");
-                break;
-            }
-        }
-        text
+        // Extract text from DOCX, with heading styles as chapters
+        extract_docx(file_path, options)
     } else if file_path.ends_with(".html") || file_path.ends_with(".htm") {
         // Extract text from HTML
-        let content = fs::read_to_string(file_path).expect("Failed to read HTML file");
-        let document = Html::parse_document(&content);
-        let selector = Selector::parse("body").unwrap();
-        document
-            .select(&selector)
-            .map(|e| e.text().collect::<Vec<_>>().join(" "))
-            .collect::<Vec<String>>()
-            .join(" ")
+        from_text_file(file_path, options, |content| html_to_document(content, options.html))
     } else if file_path.ends_with(".md") {
         // Extract text from Markdown
-        let content = fs::read_to_string(file_path).expect("Failed to read Markdown file");
-        let parser = Parser::new_ext(&content, Options::all());
-        parser
-            .filter_map(|event| match event {
-                Event::Text(t) => Some(t.to_string()),
-                _ => None,
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
+        from_text_file(file_path, options, markdown_to_document)
     } else if file_path.ends_with(".odt") {
-        // Extract text from Open Document Format
-        let file = File::open(file_path).expect("Failed to open ODT file");
-        let mut archive = ZipArchive::new(file).expect("Failed to read ODT ZIP structure");
-
-        let mut text = String::new();
-
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).expect("Failed to read ODT entry");
-            let file_name = file.name().to_string();
-
-            if file_name == "content.xml" {
-                let mut content = String::new();
-                file.read_to_string(&mut content).expect("Failed to extract XML");
-
-                let xml_parser = EventReader::from_str(&content);
-                for event in xml_parser {
-                    if let Ok(XmlEvent::Characters(chars)) = event {
-                        text.push_str(&chars);
-                        text.push(' ');
-                    }
-                }
-                break;
+        // Extract text from Open Document Format, with headings as chapters
+        extract_odt(file_path, options)
+    } else {
+        // Default to plain text files, unless an unknown file is clearly not text
+        match from_text_file(file_path, options, Document::from_text) {
+            Err(ExtractError::Encoding { path }) if !file_path.ends_with(".txt") => {
+                Err(ExtractError::UnsupportedFormat { path })
             }
+            result => result,
         }
-        text
-    } else {
-        // Default to plain text files
-        fs::read_to_string(file_path)
-            .expect("Failed to read input file")
     }
 }
 
-pub fn read_file_content(file_path: &str) -> Vec<String> {
-    get_content(file_path)
-        .split_whitespace()
-        .map(String::from)
-        .collect::<Vec<_>>()
-}
-
-pub fn read_file_sentences(file_path: &str) -> Vec<String> {
-    get_content(file_path)
-        .unicode_sentences()
-        .map(String::from)
-        .collect::<Vec<_>>()
+/// Builds a document from a text-based file with `to_document`, noting the
+/// encoding the file was in.
+fn from_text_file(
+    file_path: &str,
+    options: &ExtractOptions,
+    to_document: impl FnOnce(&str) -> Document,
+) -> Result<Document, ExtractError> {
+    let (content, encoding) = read_text(file_path, options)?;
+    let mut document = to_document(&content);
+    document.encoding = Some(encoding.to_string());
+    Ok(document)
 }
 
 
-pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
+/// Shows the load menu, with the reading streak (see [`crate::goals::streak`])
+/// in its title, and returns the path or URL of the file the user browses to
+/// or the recent book they pick. Books can be pinned from the recent list.
+pub fn load_file_menu_ui(settings: &mut Settings, streak: usize) -> Option<String> {
     let mut menu_options = vec!["Browse Files".to_string()];
-    let mut recent_files: Vec<String> = book_data.keys().cloned().collect();
-    recent_files.sort(); // Sort alphabetically for now
+    let mut recent_files = settings.recent_books();
 
     if !recent_files.is_empty() {
         menu_options.push("Recent Files".to_string());
@@ -306,6 +237,7 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
     let mut selected_index = 0;
     let mut in_recent_files_menu = false;
     let mut selected_recent_file_index = 0;
+    let mut save_error: Option<String> = None;
 
     loop {
         terminal.draw(|f| {
@@ -313,13 +245,14 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
             let items: Vec<ListItem> = if in_recent_files_menu {
                 recent_files
                     .iter()
+                    .map(|key| recent_file_entry(key, &settings.books[key]))
                     .enumerate()
                     .map(|(i, entry)| {
                         if i == selected_recent_file_index {
                             ListItem::new(format!("=> {}", entry))
                                 .style(Style::default().fg(Color::Black).bg(Color::Yellow))
                         } else {
-                            ListItem::new(entry.clone())
+                            ListItem::new(entry)
                         }
                     })
                     .collect()
@@ -338,7 +271,15 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
                     .collect()
             };
 
-            let title = if in_recent_files_menu { "Recent Files" } else { "Load File" };
+            let title = match &save_error {
+                Some(err) => err.clone(),
+                None => format!(
+                    "{} | Reading streak: {} day{}",
+                    if in_recent_files_menu { "Recent Files: [Enter] Open  [P] Pin" } else { "Load File" },
+                    streak,
+                    if streak == 1 { "" } else { "s" }
+                ),
+            };
             let list = List::new(items)
                 .block(Block::default().borders(Borders::ALL).title(title));
 
@@ -373,7 +314,8 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
                     if in_recent_files_menu {
                         terminal.clear().unwrap();
                         terminal::disable_raw_mode().unwrap();
-                        return Some(recent_files[selected_recent_file_index].clone());
+                        let key = &recent_files[selected_recent_file_index];
+                        return Some(settings.books[key].path.clone().unwrap_or_else(|| key.clone()));
                     } else {
                         match menu_options[selected_index].as_str() {
                             "Browse Files" => {
@@ -389,6 +331,15 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
                         }
                     }
                 }
+                KeyCode::Char('p') if in_recent_files_menu => {
+                    let key = recent_files[selected_recent_file_index].clone();
+                    let book = settings.books.get_mut(&key).unwrap();
+                    book.pinned = !book.pinned;
+                    save_error = settings.save().err().map(|err| err.to_string());
+                    // Keep the same book selected as it moves in the list.
+                    recent_files = settings.recent_books();
+                    selected_recent_file_index = recent_files.iter().position(|k| *k == key).unwrap_or(0);
+                }
                 KeyCode::Esc => {
                     if in_recent_files_menu {
                         in_recent_files_menu = false;
@@ -403,162 +354,50 @@ pub fn load_file_menu_ui(book_data: &HashMap<String, Value>) -> Option<String> {
     }
 }
 
-/// Save settings to a JSON file
-
-/// Load settings from the user's home directory
-pub fn load_settings() -> (u64, usize, HashMap<String, Value>) {
-    let mut speed = 300; // Default speed
-    let mut chunk_size = 1; // Default chunk size
-    let mut book_data: HashMap<String, Value> = HashMap::new();
-
-    if let Some(home) = home_dir() {
-        let settings_path = home.join(".rsvp_settings.json");
-        if settings_path.exists() {
-            let mut file = File::open(settings_path).expect("Failed to open settings file.");
-            let mut content = String::new();
-            if file.read_to_string(&mut content).is_ok() {
-                if let Ok(json_data) = serde_json::from_str::<Value>(&content) {
-                    if let Some(global) = json_data.get("global") {
-                        if let Some(s) = global.get("speed").and_then(|v| v.as_u64()) {
-                            speed = s;
-                        }
-                        if let Some(cs) = global.get("chunk_size").and_then(|v| v.as_u64()) {
-                            chunk_size = cs as usize;
-                        }
-                    }
-                    if let Some(books) = json_data.get("books").and_then(|v| v.as_object()) {
-                        for (key, value) in books {
-                            book_data.insert(key.clone(), value.clone());
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    (speed, chunk_size, book_data)
-}
-
-
-pub fn save_settings(
-    speed: u64,
-    chunk_size: usize,
-    mut book_data: HashMap<String, Value>,
-    max_saved_books: Option<u64>,
-    max_bookmarks_per_book: Option<u64>,
-) {
-    if let Some(home) = home_dir() {
-        let settings_path = home.join(".rsvp_settings.json");
-
-        // Load existing settings if they exist
-        let mut global_settings = json!({
-            "speed": speed,
-            "chunk_size": chunk_size,
-            "max_saved_books": 10, // Default
-            "max_bookmarks_per_book": 10 // Default
-        });
-
-        if settings_path.exists() {
-            if let Ok(mut file) = File::open(&settings_path) {
-                let mut content = String::new();
-                if file.read_to_string(&mut content).is_ok() {
-                    if let Ok(json_data) = serde_json::from_str::<Value>(&content) {
-                        if let Some(global) = json_data.get("global") {
-                            global_settings["max_saved_books"] =
-                                json!(max_saved_books.unwrap_or(global.get("max_saved_books").and_then(|v| v.as_u64()).unwrap_or(10)));
-
-                            global_settings["max_bookmarks_per_book"] =
-                                json!(max_bookmarks_per_book.unwrap_or(global.get("max_bookmarks_per_book").and_then(|v| v.as_u64()).unwrap_or(10)));
-                        }
-
-                        // ✅ Ensure bookmarks are retained when saving
-                        if let Some(books) = json_data.get("books").and_then(|v| v.as_object()) {
-                            for (key, value) in books {
-                                if let Some(book) = book_data.get_mut(key) {
-                                    if let Some(bookmarks) = value.get("bookmarks") {
-                                        if book["bookmarks"].is_array() {
-                                            // ✅ Ensure new bookmarks are merged with existing ones
-                                            if let Some(existing_bookmarks) = book["bookmarks"].as_array_mut() {
-                                                for bm in bookmarks.as_array().unwrap() {
-                                                    if !existing_bookmarks.contains(bm) {
-                                                        existing_bookmarks.push(bm.clone());
-                                                    }
-                                                }
-                                            }
-                                        } else {
-                                            book["bookmarks"] = bookmarks.clone();
-                                        }
-                                    }
-                                } else {
-                                    // ✅ If book entry does not exist, create it with bookmarks
-                                    book_data.insert(key.clone(), json!({ "bookmarks": value.get("bookmarks").cloned().unwrap_or(json!([])) }));
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        let settings = json!({
-            "global": global_settings,
-            "books": book_data
-        });
-
-        // ✅ Write to the JSON file
-        if let Ok(mut file) = File::create(settings_path) {
-            if let Ok(json_str) = serde_json::to_string_pretty(&settings) {
-                let _ = file.write_all(json_str.as_bytes());
-            }
-        }
+/// One line of the recent list: pin, title or file name, progress and location.
+fn recent_file_entry(key: &str, book: &BookState) -> String {
+    let location = book.path.as_deref().unwrap_or(key);
+    let name = book.title.clone().unwrap_or_else(|| {
+        Path::new(location)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| location.to_string())
+    });
+    let pin = if book.pinned { "* " } else { "" };
+    match book.progress() {
+        Some(progress) => format!("{}{} ({:.0}%) - {}", pin, name, progress * 100.0, location),
+        None => format!("{}{} - {}", pin, name, location),
     }
 }
 
-/// Adjusts the chunk size based on the length of the words.
-pub fn get_adaptive_chunk_size(
-    words: &[String],
-    current_index: usize,
-    base_chunk_size: usize,
-) -> usize {
-    if current_index >= words.len() {
-        return base_chunk_size;
+pub fn get_content_from_url(url: &str, options: &ExtractOptions) -> Result<Document, ExtractError> {
+    let network = |source| ExtractError::Network { url: url.to_string(), source };
+    let response = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(network)?;
+
+    // Only web pages and plain text can be read; PDFs and images cannot.
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("text/html")
+        .to_string();
+    if !content_type.contains("html") && !content_type.starts_with("text/") {
+        return Err(ExtractError::UnsupportedFormat { path: url.to_string() });
     }
 
-    let mut total_chars = 0;
-    let mut num_words = 0;
-
-    // A simple heuristic: try to fit a certain number of characters in a chunk.
-    // Let's aim for an average of 5 characters per word in the base chunk size.
-    let target_chars = base_chunk_size * 5;
-
-    for i in 0..base_chunk_size * 2 { // Check up to twice the base chunk size
-        if current_index + i < words.len() {
-            total_chars += words[current_index + i].len();
-            num_words += 1;
-            if total_chars > target_chars {
-                break;
-            }
-        } else {
-            break;
-        }
-    }
-
-    num_words.max(1) // Always return at least 1.
+    let body = response.text().map_err(network)?;
+    Ok(html_to_document(&body, options.html))
 }
 
-pub fn get_content_from_url(url: &str) -> Result<String, reqwest::Error> {
-    let body = reqwest::blocking::get(url)?.text()?;
-    let document = Html::parse_document(&body);
-    let selector = Selector::parse("body").unwrap();
-    let text = document
-        .select(&selector)
-        .map(|e| e.text().collect::<Vec<_>>().join(" "))
-        .collect::<Vec<String>>()
-        .join(" ");
-    Ok(text)
+pub fn get_url_ui() -> Option<String> {
+    get_text_ui("Enter URL", "")
 }
 
-pub fn get_url_ui() -> Option<String> {
+/// Asks for a line of text, starting from `initial`. Returns `None` when
+/// cancelled with Esc.
+pub fn get_text_ui(title: &str, initial: &str) -> Option<String> {
     let backend = ratatui::backend::CrosstermBackend::new(stdout());
     let mut terminal = Terminal::new(backend).unwrap();
 
@@ -573,13 +412,13 @@ pub fn get_url_ui() -> Option<String> {
     terminal::enable_raw_mode().unwrap();
     terminal.clear().unwrap();
 
-    let mut url = String::new();
+    let mut text = initial.to_string();
 
     loop {
         terminal.draw(|f| {
             let size = f.area();
-            let input = Paragraph::new(url.as_str())
-                .block(Block::default().borders(Borders::ALL).title("Enter URL"));
+            let input = Paragraph::new(text.as_str())
+                .block(Block::default().borders(Borders::ALL).title(title));
             f.render_widget(input, size);
         }).unwrap();
 
@@ -588,13 +427,13 @@ pub fn get_url_ui() -> Option<String> {
                 KeyCode::Enter => {
                     terminal.clear().unwrap();
                     terminal::disable_raw_mode().unwrap();
-                    return Some(url);
+                    return Some(text);
                 }
                 KeyCode::Char(c) => {
-                    url.push(c);
+                    text.push(c);
                 }
                 KeyCode::Backspace => {
-                    url.pop();
+                    text.pop();
                 }
                 KeyCode::Esc => {
                     terminal::disable_raw_mode().unwrap();